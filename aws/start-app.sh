@@ -1,5 +1,12 @@
 #!/bin/bash
 
+mkdir -p /home/ec2-user/app/data
+
+# deposits and withdrawals need the operator key, without it they are refused
+if [ -f /home/ec2-user/app/operator.key ]; then
+    export ZSONKORP_OPERATOR_KEY=$(cat /home/ec2-user/app/operator.key)
+fi
+
 #pipe to dev null for now, need to hook up tp cloud watch
-nohup /home/ec2-user/app/bin/zsonkorp > /dev/null 2>&1 &
+ZSONKORP_JOURNAL=/home/ec2-user/app/data/games.jsonl ZSONKORP_LEDGER=/home/ec2-user/app/data/ledger.jsonl nohup /home/ec2-user/app/bin/zsonkorp > /dev/null 2>&1 &
 exit 0
\ No newline at end of file
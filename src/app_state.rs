@@ -1,15 +1,168 @@
-use std::sync::{Arc, Mutex};
-use crate::game_storage::GameStorage;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::Result;
+use crate::expiry::Evictor;
+use crate::game::{Game, WagerChange};
+use crate::game_storage::{FileStorage, GameStorage, MemoryStorage, StoredGame};
+use crate::ledger::Ledger;
+use crate::payout::Payout;
 
 #[derive(Clone)]
-pub(crate) struct AppState {
-    pub game_store: Arc<Mutex<GameStorage>>
+pub struct AppState {
+    pub(crate) game_store: Arc<dyn GameStorage>,
+    pub(crate) evictor: Arc<Evictor>,
+    pub(crate) ledger: Option<Arc<Ledger>>,
+    pub(crate) operator_key: Option<Arc<str>>
 }
 
 impl AppState {
+    // Games are lost on restart
     pub fn new() -> Self {
-        AppState {
-            game_store: Arc::new(Mutex::new(GameStorage::new()))
+        AppState::with_storage(Arc::new(MemoryStorage::new()))
+    }
+
+    // Games are journaled to `path` and reloaded from it
+    pub fn with_journal(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(AppState::with_storage(Arc::new(FileStorage::open(path)?)))
+    }
+
+    pub fn with_evictor(self, evictor: Evictor) -> Self {
+        AppState { evictor: Arc::new(evictor), ..self }
+    }
+
+    // Ended games settle into the ledger, the ones that ended before it was attached settle right away. New games
+    // escrow the max loss of their wagers.
+    pub fn with_ledger(self, ledger: Ledger) -> Result<Self> {
+        let app_state = AppState { ledger: Some(Arc::new(ledger)), ..self };
+        app_state.settle_ended_games()?;
+        Ok(app_state)
+    }
+
+    // Games can only be voided by requests carrying this key, no one can void them without it
+    pub fn with_operator_key(self, operator_key: String) -> Self {
+        AppState { operator_key: Some(operator_key.into()), ..self }
+    }
+
+    fn with_storage(game_store: Arc<dyn GameStorage>) -> Self {
+        AppState { game_store, evictor: Arc::new(Evictor::default()), ledger: None, operator_key: None }
+    }
+
+    // Reserves the max losses of a new game, the game is removed when the players or the house cannot cover them
+    pub(crate) fn escrow(&self, id: &str) -> Result<()> {
+        let (Some(ledger), Some(game_ref)) = (&self.ledger, self.game_store.get_game(id)) else {
+            return Ok(());
+        };
+
+        let escrowed = {
+            let stored = game_ref.lock().unwrap();
+            max_losses_of(stored.get_game()).and_then(|max_losses| ledger.escrow(id, &max_losses))
+        };
+
+        if let Err(e) = escrowed {
+            self.game_store.remove_game(id)?;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    // The escrow of the game follows its wagers, changes the players or the house cannot cover are refused
+    pub(crate) fn change_wagers(&self, id: &str, change: WagerChange) -> Result<()> {
+        self.game_store.change_wagers(id, change, &mut |game| {
+            if let Some(ledger) = &self.ledger {
+                ledger.escrow(id, &max_losses_of(game)?)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    // Settles the game if it has ended or was voided, settling twice is a no-op
+    pub(crate) fn settle(&self, id: &str) -> Result<()> {
+        let (Some(ledger), Some(game_ref)) = (&self.ledger, self.game_store.get_game(id)) else {
+            return Ok(());
+        };
+
+        let stored = game_ref.lock().unwrap();
+        let game = stored.get_game();
+
+        if game.get_state().is_over() {
+            ledger.settle(id, &game.get_payout()?)?;
+        }
+
+        Ok(())
+    }
+
+    // Catches up on settlements that failed. Eviction settles the games it removes on its own.
+    pub(crate) fn settle_ended_games(&self) -> Result<()> {
+        for (id, _) in self.game_store.list_games() {
+            self.settle(&id)?;
         }
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    // Run by the evictor under the game lock before the game goes: a finished game settles, an unstarted one
+    // gets its escrow back. Games being played are never evicted.
+    fn close_books(&self, id: &str, stored: &StoredGame) -> Result<()> {
+        let Some(ledger) = &self.ledger else {
+            return Ok(());
+        };
+
+        let game = stored.get_game();
+        if game.get_state().is_over() {
+            ledger.settle(id, &game.get_payout()?)?;
+        } else {
+            ledger.release(id)?;
+        }
+
+        Ok(())
+    }
+
+    // Creates a game through `create` once the evictor made room for it
+    pub(crate) fn admit(&self, create: impl FnOnce() -> Result<String>) -> Result<String> {
+        self.evictor.admit(self.game_store.as_ref(), &|id, stored| self.close_books(id, stored), create)
+    }
+
+    pub(crate) fn evict_expired(&self) -> Result<usize> {
+        self.evictor.evict_expired(self.game_store.as_ref(), &|id, stored| self.close_books(id, stored))
+    }
+
+    // Background task evicting expired games, runs until the runtime shuts down. A sweep locks games and syncs
+    // files, it runs on the blocking pool rather than on the async workers.
+    pub async fn evict_expired_games(self) {
+        let mut interval = tokio::time::interval(self.evictor.get_interval());
+
+        loop {
+            interval.tick().await;
+
+            let state = self.clone();
+            let sweep = tokio::task::spawn_blocking(move || {
+                if let Err(e) = state.settle_ended_games() {
+                    eprintln!("Game settlement failed: {}", e);
+                }
+
+                if let Err(e) = state.evict_expired() {
+                    eprintln!("Game eviction failed: {}", e);
+                }
+            });
+
+            if let Err(e) = sweep.await {
+                eprintln!("Game sweep failed: {}", e);
+            }
+        }
+    }
+}
+
+// What the players and the house stand to lose at worst
+fn max_losses_of(game: &dyn Game) -> Result<Vec<Payout<'_>>> {
+    let mut max_losses = game.get_max_losses()?;
+    max_losses.extend(game.get_house_max_losses()?);
+    Ok(max_losses)
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        AppState::new()
+    }
+}
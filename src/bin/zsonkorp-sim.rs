@@ -0,0 +1,38 @@
+use std::process::ExitCode;
+use zsonkorp::sim;
+
+const DEFAULT_ROUNDS: u64 = 100_000;
+
+// Usage: zsonkorp-sim <Fts|Cta> <config.json> [rounds]
+// The config is the body that would be sent to POST /game?kind=<Fts|Cta>
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: zsonkorp-sim <Fts|Cta> <config.json> [rounds, default {}]", DEFAULT_ROUNDS);
+        return ExitCode::FAILURE;
+    }
+
+    let result = std::fs::read_to_string(&args[1])
+        .map_err(anyhow::Error::from)
+        .and_then(|config| {
+            let rounds = match args.get(2) {
+                Some(rounds) => rounds.parse()?,
+                None => DEFAULT_ROUNDS
+            };
+
+            sim::run(&args[0], &config, rounds)
+        })
+        .and_then(|report| Ok(serde_json::to_string_pretty(&report)?));
+
+    match result {
+        Ok(report) => {
+            println!("{}", report);
+            ExitCode::SUCCESS
+        },
+        Err(e) => {
+            eprintln!("Simulation failed: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt;
 use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 use crate::card;
 
@@ -14,13 +16,24 @@ pub fn get_suit_count() -> u8 {
     SUIT_COUNT
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(try_from = "CardRepr")]
 pub(crate) struct Card {
     val: u8
 }
+
+// Short notation, rank then suit letter: AS, 10H, QD
 impl fmt::Display for Card {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "Card {{ val: {}, rank: {}, suit: {} }}", self.val, self.get_rank(), self.get_suit())
+        let rank = match self.get_rank() {
+            1 => "A".to_string(),
+            11 => "J".to_string(),
+            12 => "Q".to_string(),
+            13 => "K".to_string(),
+            rank => rank.to_string()
+        };
+
+        write!(f, "{}{}", rank, self.get_suit())
     }
 }
 impl fmt::Debug for Card {
@@ -37,10 +50,47 @@ pub enum Suit {
     Diamonds
 }
 
-
 impl fmt::Display for Suit {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        let letter = match self {
+            Suit::Hearts => 'H',
+            Suit::Spades => 'S',
+            Suit::Clubs => 'C',
+            Suit::Diamonds => 'D'
+        };
+
+        write!(f, "{}", letter)
+    }
+}
+
+// Either the suit letter or its full name, case insensitive
+impl FromStr for Suit {
+    type Err = card::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "H" | "HEARTS" => Ok(Suit::Hearts),
+            "S" | "SPADES" => Ok(Suit::Spades),
+            "C" | "CLUBS" => Ok(Suit::Clubs),
+            "D" | "DIAMONDS" => Ok(Suit::Diamonds),
+            _ => Err(Error::InvalidNotation(s.to_string()))
+        }
+    }
+}
+
+// Written as its letter, the same as in card notation
+impl Serialize for Suit {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Suit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
     }
 }
 
@@ -49,7 +99,9 @@ pub enum Error {
     #[error("Invalid suit ordinal value: {0}")]
     SuitOrdinal(u8),
     #[error("Invalid rank value: {0}")]
-    InvalidRank(u8)
+    InvalidRank(u8),
+    #[error("Invalid card notation: {0}")]
+    InvalidNotation(String)
 }
 
 impl TryFrom<u8> for Suit {
@@ -76,6 +128,54 @@ impl From<Suit> for u8 {
     }
 }
 
+// The last character is the suit, the rest the rank: A, 2 to 10 (or T), J, Q, K
+impl FromStr for Card {
+    type Err = card::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidNotation(s.to_string());
+
+        let split = s.len().checked_sub(1).filter(|split| s.is_char_boundary(*split)).ok_or_else(invalid)?;
+        let (rank, suit) = s.split_at(split);
+
+        let rank = match rank.to_ascii_uppercase().as_str() {
+            "A" => 1,
+            "T" => 10,
+            "J" => 11,
+            "Q" => 12,
+            "K" => 13,
+            rank => rank.parse().ok().filter(|rank| (2..=10).contains(rank)).ok_or_else(invalid)?
+        };
+
+        Card::new(rank, suit.parse().map_err(|_| invalid())?)
+    }
+}
+
+impl Serialize for Card {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.collect_str(self)
+    }
+}
+
+// Cards are read from their short notation or as a rank and suit pair
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CardRepr {
+    Notation(String),
+    Structured { rank: u8, suit: Suit }
+}
+
+impl TryFrom<CardRepr> for Card {
+    type Error = card::Error;
+    fn try_from(value: CardRepr) -> Result<Self, Self::Error> {
+        match value {
+            CardRepr::Notation(notation) => notation.parse(),
+            CardRepr::Structured { rank, suit } => Card::new(rank, suit)
+        }
+    }
+}
+
 impl Card {
     pub fn new(rank: u8, suit: Suit) -> Result<Card, card::Error> {
 
@@ -238,4 +338,48 @@ mod tests {
         let card = Card { val: 12 };
         assert_eq!(card.get_suit(), Suit::Hearts);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn notation() -> Result<(), card::Error> {
+        assert_eq!(Card::new(1, Suit::Spades)?.to_string(), "AS");
+        assert_eq!(Card::new(10, Suit::Hearts)?.to_string(), "10H");
+        assert_eq!(Card::new(12, Suit::Diamonds)?.to_string(), "QD");
+        assert_eq!(Card::new(7, Suit::Clubs)?.to_string(), "7C");
+
+        assert_eq!("AS".parse::<Card>()?, Card::new(1, Suit::Spades)?);
+        assert_eq!("th".parse::<Card>()?, Card::new(10, Suit::Hearts)?);
+        assert_eq!("kc".parse::<Card>()?, Card::new(13, Suit::Clubs)?);
+        Ok(())
+    }
+
+    #[test]
+    fn notation_round_trip() -> Result<(), card::Error> {
+        for val in 0..RANK_COUNT * SUIT_COUNT {
+            let card = Card { val };
+            assert_eq!(card.to_string().parse::<Card>()?, card);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_notation() {
+        for notation in ["", "A", "1H", "11S", "AX", "0D", "ÄS", "10"] {
+            assert_eq!(notation.parse::<Card>().unwrap_err(), Error::InvalidNotation(notation.to_string()));
+        }
+    }
+
+    #[test]
+    fn serde_forms() -> Result<(), serde_json::Error> {
+        let card = Card::new(10, Suit::Hearts).unwrap();
+
+        assert_eq!(serde_json::to_string(&card)?, r#""10H""#);
+        assert_eq!(serde_json::to_string(&card.get_suit())?, r#""H""#);
+        assert_eq!(serde_json::from_str::<Card>(r#""10H""#)?, card);
+        assert_eq!(serde_json::from_str::<Card>(r#"{ "rank": 10, "suit": "Hearts" }"#)?, card);
+        assert_eq!(serde_json::from_str::<Card>(r#"{ "rank": 10, "suit": "H" }"#)?, card);
+
+        assert!(serde_json::from_str::<Card>(r#""1H""#).is_err());
+        assert!(serde_json::from_str::<Card>(r#"{ "rank": 14, "suit": "Hearts" }"#).is_err());
+        Ok(())
+    }
+}
@@ -1,16 +1,18 @@
 pub mod fts;
 mod cta;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 pub use cta::Cta;
 pub use cta::CtaWagerType;
 pub use fts::Fts;
 pub use fts::FtsWagerType;
 use crate::player::Player;
-use anyhow::{anyhow, Result};
-use serde::Deserialize;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use crate::config::ConfigError::DuplicatedWagerId;
+use crate::money::{Currency, Money};
+use crate::validation::{pointer, Violations};
 use crate::wager::Wager;
 
 #[derive(Error, Debug)]
@@ -18,51 +20,173 @@ enum ConfigError {
     #[error("Wagers must not be empty")]
     EmptyWager,
 
-    #[error("Players: {0} have no wagers")]
-    EmptyWagerForPlayers(String),
+    #[error("Player has no wagers")]
+    EmptyWagerForPlayer,
 
     #[error("Duplicated wager id: {0}")]
-    DuplicatedWagerId(u32)
+    DuplicatedWagerId(u32),
+
+    #[error("Wager amount must be positive, got {0}")]
+    NonPositiveAmount(Money),
+
+    #[error("{0} wager amounts add up past the largest supported amount")]
+    StakeOverflow(Currency),
+
+    #[error("Wager currency {0} differs from the game currency {1}")]
+    MixedCurrencies(Currency, Currency),
+
+    #[error("Wager amount {} is below the {1} minimum stake of {}", .0.in_currency(.1), .2.in_currency(.1))]
+    StakeBelowMinimum(Money, Currency, Money),
+
+    #[error("Wager amount {} is above the {1} maximum stake of {}", .0.in_currency(.1), .2.in_currency(.1))]
+    StakeAboveMaximum(Money, Currency, Money),
+
+    #[error("Stake limits must be positive, got {0}")]
+    NonPositiveStakeLimit(Money),
+
+    #[error("Maximum stake {0} is below the minimum stake {1}")]
+    ReversedStakeLimits(Money, Money),
+
+    #[error("House id must not be empty")]
+    EmptyHouseId
+}
+
+pub type WagerMap<T> = HashMap<Player, Vec<Wager<T>>>;
+
+// Amounts a single wager of a currency may stake, both bounds are inclusive
+#[derive(Clone, Deserialize, Serialize)]
+pub struct StakeLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min: Option<Money>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max: Option<Money>
+}
+
+// Read from the same object as the wagers, every wager shares a single currency unless `mixed_currencies` is set
+#[derive(Clone, Deserialize, Serialize, Default)]
+pub struct CurrencyRules {
+    #[serde(default)]
+    mixed_currencies: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    stake_limits: BTreeMap<Currency, StakeLimits>
+}
+
+impl CurrencyRules {
+    fn validate(&self) -> Violations {
+        let mut violations = Violations::new();
+
+        for (currency, limits) in &self.stake_limits {
+            let limit_pointer = |field: &str| pointer(&["stake_limits", &currency.to_string(), field]);
+
+            for (field, limit) in [("min", limits.min), ("max", limits.max)] {
+                if let Some(limit) = limit.filter(|limit| !limit.is_positive()) {
+                    violations.add(limit_pointer(field), ConfigError::NonPositiveStakeLimit(limit));
+                }
+            }
+
+            if let (Some(min), Some(max)) = (limits.min, limits.max) {
+                if max < min {
+                    violations.add(limit_pointer("max"), ConfigError::ReversedStakeLimits(max, min));
+                }
+            }
+        }
+
+        violations
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Config<T> {
-    wagers: HashMap<Player, Vec<Wager<T>>>,
-    house_id: String
+    wagers: WagerMap<T>,
+    house_id: String,
+    #[serde(flatten)]
+    currency_rules: CurrencyRules
 }
 
 impl<T> Config<T> {
-    pub fn new(wagers: HashMap<Player, Vec<Wager<T>>>, house_id: String) -> Result<Self> {
+    // `violations` holds what the game config found, they are reported together with the shared ones
+    pub fn new(wagers: HashMap<Player, Vec<Wager<T>>>,
+               house_id: String,
+               currency_rules: CurrencyRules,
+               mut violations: Violations) -> Result<Self> {
+
+        violations.append(Config::validate(&wagers, &house_id, &currency_rules));
+        violations.into_result()?;
+
+        Ok(Config { wagers, house_id, currency_rules })
+    }
+
+    // Problems shared by every game type
+    fn validate(wagers: &WagerMap<T>, house_id: &str, currency_rules: &CurrencyRules) -> Violations {
+        let mut violations = currency_rules.validate();
+
         if wagers.is_empty() {
-            return Err(anyhow!(ConfigError::EmptyWager));
+            violations.add(pointer(&["wagers"]), ConfigError::EmptyWager);
+        }
+
+        if house_id.is_empty() {
+            violations.add(pointer(&["house_id"]), ConfigError::EmptyHouseId);
         }
 
-        let mut empty_player: Vec<&Player> = Vec::new();
         let mut wager_ids: Vec<u32> = Vec::new();
+        let mut stakes: BTreeMap<&Currency, Option<Money>> = BTreeMap::new();
+        let mut game_currency: Option<&Currency> = None;
 
-        for (player, wagers) in &wagers {
+        // players in id order so that the same duplicate is reported every time
+        for (player, wagers) in Config::sorted(wagers) {
             if wagers.is_empty() {
-                empty_player.push(player);
-            } else {
-                for wager in wagers {
-                    let id = wager.get_id();
-
-                    if wager_ids.contains(id) {
-                        return Err(anyhow!(DuplicatedWagerId(*id)));
-                    } else {
-                        wager_ids.push(*id);
+                violations.add(pointer(&["wagers", player.get_id()]), ConfigError::EmptyWagerForPlayer);
+            }
+
+            for (i, wager) in wagers.iter().enumerate() {
+                let id = wager.get_id();
+
+                if wager_ids.contains(id) {
+                    violations.add(wager_pointer(player, i, "id"), DuplicatedWagerId(*id));
+                } else {
+                    wager_ids.push(*id);
+                }
+
+                let currency = wager.get_currency();
+
+                // the first wager sets the currency of the game
+                let game_currency = *game_currency.get_or_insert(currency);
+                if !currency_rules.mixed_currencies && currency != game_currency {
+                    violations.add(
+                        wager_pointer(player, i, "currency"),
+                        ConfigError::MixedCurrencies(currency.clone(), game_currency.clone())
+                    );
+                }
+
+                if !wager.amount.is_positive() {
+                    violations.add(wager_pointer(player, i, "amount"), ConfigError::NonPositiveAmount(wager.amount));
+                } else if let Some(limits) = currency_rules.stake_limits.get(currency) {
+                    if let Some(min) = limits.min.filter(|min| wager.amount < *min) {
+                        violations.add(wager_pointer(player, i, "amount"), ConfigError::StakeBelowMinimum(wager.amount, currency.clone(), min));
+                    }
+
+                    if let Some(max) = limits.max.filter(|max| wager.amount > *max) {
+                        violations.add(wager_pointer(player, i, "amount"), ConfigError::StakeAboveMaximum(wager.amount, currency.clone(), max));
                     }
                 }
+
+                let stake = stakes.entry(currency).or_insert(Some(Money::ZERO));
+                *stake = stake.and_then(|stake| stake.checked_add(wager.amount).ok());
             }
         }
 
-        if !empty_player.is_empty() {
-            return Err(anyhow!(ConfigError::EmptyWagerForPlayers(
-                    format!("Players: {:?} have no wagers", empty_player)
-            )));
+        // the house line of an even money game is the sum of the stakes of each currency
+        for (currency, _) in stakes.into_iter().filter(|(_, stake)| stake.is_none()) {
+            violations.add(pointer(&["wagers"]), ConfigError::StakeOverflow(currency.clone()));
         }
 
-        Ok(Config { wagers, house_id })
+        violations
+    }
+
+    fn sorted(wagers: &WagerMap<T>) -> Vec<(&Player, &Vec<Wager<T>>)> {
+        let mut sorted: Vec<(&Player, &Vec<Wager<T>>)> = wagers.iter().collect();
+        sorted.sort_by(|a, b| a.0.get_id().cmp(b.0.get_id()));
+        sorted
     }
 
     pub fn get_wagers(&self) -> &HashMap<Player, Vec<Wager<T>>> {
@@ -72,4 +196,60 @@ impl<T> Config<T> {
     pub fn get_house_id(&self) -> &str {
         &self.house_id
     }
+
+    pub fn get_currency_rules(&self) -> &CurrencyRules {
+        &self.currency_rules
+    }
+}
+
+// Pointer to a field of the ith wager of a player
+fn wager_pointer(player: &Player, i: usize, field: &str) -> String {
+    pointer(&["wagers", player.get_id(), &i.to_string(), field])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::validation::{ValidationErrors, Violation};
+    use super::*;
+
+    fn violations(wagers: &str, rules: &str) -> Vec<Violation> {
+        let wagers: Vec<Wager<CtaWagerType>> = serde_json::from_str(wagers).unwrap();
+        let rules: CurrencyRules = serde_json::from_str(rules).unwrap();
+
+        match Config::new(HashMap::from([(Player::new("player1".to_string()), wagers)]), "house".to_string(), rules, Violations::new()) {
+            Ok(_) => Vec::new(),
+            Err(e) => e.downcast::<ValidationErrors>().unwrap().0
+        }
+    }
+
+    const MIXED: &str = r#"[
+        { "id": 0, "wager_type": "Forward", "amount": 100, "currency": "EUR" },
+        { "id": 1, "wager_type": "Reverse", "amount": 100, "currency": "USD" }
+    ]"#;
+
+    #[test]
+    fn mixed_currencies() {
+        let rejected = violations(MIXED, "{}");
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].path, "/wagers/player1/1/currency");
+
+        assert!(violations(MIXED, r#"{ "mixed_currencies": true }"#).is_empty());
+    }
+
+    #[test]
+    fn stake_limits() {
+        let rules = r#"{ "mixed_currencies": true, "stake_limits": { "EUR": { "min": 500 }, "USD": { "max": 50 } } }"#;
+        let paths: Vec<String> = violations(MIXED, rules).into_iter().map(|violation| violation.path).collect();
+        assert_eq!(paths, vec!["/wagers/player1/0/amount", "/wagers/player1/1/amount"]);
+
+        // limits of other currencies do not apply
+        assert!(violations(MIXED, r#"{ "mixed_currencies": true, "stake_limits": { "GBP": { "max": 1 } } }"#).is_empty());
+    }
+
+    #[test]
+    fn invalid_stake_limits() {
+        let rules = r#"{ "mixed_currencies": true, "stake_limits": { "EUR": { "min": 0 }, "USD": { "min": 100, "max": 10 } } }"#;
+        let paths: Vec<String> = violations(MIXED, rules).into_iter().map(|violation| violation.path).collect();
+        assert_eq!(paths, vec!["/stake_limits/EUR/min", "/stake_limits/USD/max", "/wagers/player1/1/amount"]);
+    }
 }
\ No newline at end of file
@@ -1,28 +1,57 @@
 use std::collections::HashMap;
-use crate::config::Config;
+use crate::card;
+use crate::config::{Config, CurrencyRules};
+use crate::validation::{pointer, Violations};
 use crate::wager::Wager;
 use anyhow::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use crate::player::Player;
 
-#[derive(Eq, PartialEq, Deserialize)]
+// Forward wins when the first ace is reached reading down from the cut,
+// Reverse wins when it is reached reading back up, both are even money
+#[derive(Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub enum CtaWagerType {
     Forward,
     Reverse
 }
 
+// Serialized in the same shape as the game configuration
+#[derive(Serialize, Deserialize)]
 pub struct Cta {
-    base_config: Config<CtaWagerType>
+    #[serde(flatten)]
+    base_config: Config<CtaWagerType>,
+    #[serde(rename = "cuts")]
+    cut_count: u8       // the last cut decides the outcome, earlier ones only split the pool
 }
 
 impl Cta {
-    pub fn new(wagers: HashMap<Player, Vec<Wager<CtaWagerType>>>, house_id: String) -> Result<Self> {
-        let base_config = Config::new(wagers, house_id)?;
+    pub fn new(wagers: HashMap<Player, Vec<Wager<CtaWagerType>>>,
+               house_id: String,
+               currency_rules: CurrencyRules,
+               opt_cut_count: Option<u8>) -> Result<Self> {
 
-        Ok(Cta{ base_config })
+        let cut_count = opt_cut_count.unwrap_or(1);
+        let mut violations = Violations::new();
+
+        // every cut needs a sub-deck of at least 2 cards to split
+        let card_count = card::get_rank_count() * card::get_suit_count();
+
+        if cut_count == 0 {
+            violations.add(pointer(&["cuts"]), "At least one cut is required");
+        } else if cut_count >= card_count {
+            violations.add(pointer(&["cuts"]), format!("{} cuts cannot be made in a deck of {} cards", cut_count, card_count));
+        }
+
+        let base_config = Config::new(wagers, house_id, currency_rules, violations)?;
+
+        Ok(Cta{ base_config, cut_count })
     }
 
     pub fn get_base_config(&self) -> &Config<CtaWagerType> {
         &self.base_config
     }
+
+    pub fn get_cut_count(&self) -> u8 {
+        self.cut_count
+    }
 }
\ No newline at end of file
@@ -1,14 +1,69 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use crate::player::Player;
 use anyhow::Result;
-use serde::Deserialize;
-use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::card;
+use crate::config::{Config, CurrencyRules, WagerMap};
+use crate::game::fts::probability;
+use crate::money::{Currency, Money};
+use crate::validation::{pointer, Violations};
 use crate::wager::Wager;
 
+pub const MIN_ODDS: i32 = 1;
+pub const MAX_ODDS: i32 = 1000;
+
+#[derive(Error, Debug)]
+enum FtsConfigError {
+    #[error("Odds must be between {MIN_ODDS} and {MAX_ODDS}, got {0}")]
+    OddsOutOfBounds(i32),
+
+    #[error("Flop range paytable entries must cover at least one flop")]
+    EmptyFlopRangeEntry,
+
+    #[error("Duplicated flop range paytable entry: length {0}, start {1:?}")]
+    DuplicatedFlopRangeEntry(u16, Option<u8>),
+
+    #[error("Flop {0} is past the last flop of the deck ({1})")]
+    FlopPastDeck(u8, u8),
+
+    #[error("Flop range starts at {0} after its end {1}")]
+    ReversedFlopRange(u8, u8),
+
+    #[error("Wager amount {0} times {1} overflows the largest supported amount")]
+    PayoutOverflow(Money, i64),
+
+    #[error("{0} wager payouts add up past the largest supported amount")]
+    HousePayoutOverflow(Currency)
+}
+
+// Flops dealt from a full single deck
+pub fn get_max_flop_count() -> u8 {
+    card::get_rank_count() * card::get_suit_count() / 3
+}
+
+// Flops covered by an inclusive range, wide enough for any bounds. Callers validate that ranges are not reversed.
+pub fn get_flop_range_length(flop_start: u8, flop_end: u8) -> u16 {
+    debug_assert!(flop_start <= flop_end, "reversed flop range {}..={}", flop_start, flop_end);
+    u16::from(flop_end).saturating_sub(u16::from(flop_start)) + 1
+}
+
+// Flop range odds for ranges of `length` flops, only for ranges beginning at `start` when it is set
+#[derive(Clone, Deserialize, Serialize)]
+pub struct FlopRangeOdds {
+    length: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start: Option<u8>,
+    odds: i32
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Odds {
     full_deck: i32,
     at_flop: i32,
-    flop_range: i32
+    flop_range: i32,                            // used when no paytable entry matches the range
+    #[serde(default)]
+    flop_range_table: Vec<FlopRangeOdds>
 }
 
 impl Odds {
@@ -20,8 +75,59 @@ impl Odds {
         &self.at_flop
     }
 
-    pub fn get_flop_range(&self) -> &i32 {
-        &self.flop_range
+    // Entries matching both the length and the start of the range take precedence over length only ones
+    pub fn get_flop_range_odds(&self, flop_start: u8, flop_end: u8) -> i32 {
+        let length = get_flop_range_length(flop_start, flop_end);
+
+        self.flop_range_table.iter()
+            .find(|entry| entry.length == length && entry.start == Some(flop_start))
+            .or_else(|| self.flop_range_table.iter().find(|entry| entry.length == length && entry.start.is_none()))
+            .map_or(self.flop_range, |entry| entry.odds)
+    }
+
+    // Sets the odds paying `wager_type`, a flop range gets a paytable entry for its exact length and start
+    pub fn set_odds(&mut self, wager_type: &FtsWagerType, odds: i32) {
+        match wager_type {
+            FtsWagerType::FullDeck => self.full_deck = odds,
+            FtsWagerType::AtFlop(_) => self.at_flop = odds,
+            FtsWagerType::FlopRange(flop_start, flop_end) => {
+                let length = get_flop_range_length(*flop_start, *flop_end);
+
+                self.flop_range_table.retain(|entry| entry.length != length || entry.start != Some(*flop_start));
+                self.flop_range_table.push(FlopRangeOdds { length, start: Some(*flop_start), odds });
+            }
+        }
+    }
+
+    fn validate(&self) -> Violations {
+        let mut violations = Violations::new();
+
+        for (name, odds) in [("full_deck", self.full_deck), ("at_flop", self.at_flop), ("flop_range", self.flop_range)] {
+            if !(MIN_ODDS..=MAX_ODDS).contains(&odds) {
+                violations.add(pointer(&["odds", name]), FtsConfigError::OddsOutOfBounds(odds));
+            }
+        }
+
+        for (i, entry) in self.flop_range_table.iter().enumerate() {
+            let entry_pointer = |field: &str| pointer(&["odds", "flop_range_table", &i.to_string(), field]);
+
+            if entry.length == 0 {
+                violations.add(entry_pointer("length"), FtsConfigError::EmptyFlopRangeEntry);
+            }
+
+            if !(MIN_ODDS..=MAX_ODDS).contains(&entry.odds) {
+                violations.add(entry_pointer("odds"), FtsConfigError::OddsOutOfBounds(entry.odds));
+            }
+
+            if self.flop_range_table[..i].iter().any(|other| other.length == entry.length && other.start == entry.start) {
+                violations.add(
+                    pointer(&["odds", "flop_range_table", &i.to_string()]),
+                    FtsConfigError::DuplicatedFlopRangeEntry(entry.length, entry.start)
+                );
+            }
+        }
+
+        violations
     }
 }
 
@@ -30,31 +136,151 @@ impl Default for Odds {
         Odds {
             full_deck: 17,
             at_flop: 17,
-            flop_range: 17
+            flop_range: 17,
+            flop_range_table: Vec::new()
         }
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub enum FtsWagerType {
     FullDeck,
     AtFlop(u8),             // This is 0 based
     FlopRange(u8, u8)       // This is also 0 based, inclusive
 }
 
+impl FtsWagerType {
+    // Flops must be dealt from a full single deck and ranges must not be reversed
+    pub fn validate(&self, path: String) -> Violations {
+        let mut violations = Violations::new();
+
+        if let Some(error) = self.get_error() {
+            violations.add(path, error);
+        }
+
+        violations
+    }
+
+    fn get_error(&self) -> Option<FtsConfigError> {
+        let max_flop_count = get_max_flop_count();
+
+        match *self {
+            FtsWagerType::FullDeck => None,
+            FtsWagerType::AtFlop(ith) if ith >= max_flop_count =>
+                Some(FtsConfigError::FlopPastDeck(ith, max_flop_count - 1)),
+            FtsWagerType::FlopRange(start, end) if start > end =>
+                Some(FtsConfigError::ReversedFlopRange(start, end)),
+            FtsWagerType::FlopRange(_, end) if end >= max_flop_count =>
+                Some(FtsConfigError::FlopPastDeck(end, max_flop_count - 1)),
+            _ => None
+        }
+    }
+
+    // Largest multiple of the wager amount that can be won or lost, a flop range must not be reversed
+    fn get_max_multiplier(&self, odds: &Odds) -> i64 {
+        match *self {
+            FtsWagerType::FullDeck => std::cmp::max(i64::from(odds.full_deck), i64::from(get_max_flop_count())),
+            FtsWagerType::AtFlop(_) => std::cmp::max(i64::from(odds.at_flop), 1),
+            FtsWagerType::FlopRange(start, end) =>
+                std::cmp::max(i64::from(odds.get_flop_range_odds(start, end)), i64::from(get_flop_range_length(start, end)))
+        }
+    }
+}
+
+// Serialized in the same shape as the game configuration, odds always explicit
+#[derive(Serialize, Deserialize)]
 pub struct Fts {
+    #[serde(flatten)]
     base_config: Config<FtsWagerType>,
-    odds: Odds
+    odds: Odds,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    house_edge: Option<f64>     // set when the odds are auto priced, they are priced again when the wagers change
 }
 
 impl Fts {
     pub fn new(wagers: HashMap<Player, Vec<Wager<FtsWagerType>>>,
                house_id: String,
+               currency_rules: CurrencyRules,
                opt_odds: Option<Odds>) -> Result<Self> {
 
-        let base_config = Config::new(wagers, house_id)?;
+        let odds = opt_odds.unwrap_or_default();
+
+        let mut violations = Fts::validate_wager_types(&wagers);
+        violations.append(odds.validate());
+        violations.append(Fts::validate_payouts(&wagers, &odds));
+
+        let base_config = Config::new(wagers, house_id, currency_rules, violations)?;
+
+        Ok( Fts{ base_config, odds, house_edge: None })
+    }
+
+    // Odds giving the house `house_edge` over these wagers, the wagers are validated before being priced
+    pub fn auto_priced(wagers: HashMap<Player, Vec<Wager<FtsWagerType>>>,
+                       house_id: String,
+                       currency_rules: CurrencyRules,
+                       house_edge: f64) -> Result<Self> {
+
+        let mut config = Fts::new(wagers, house_id, currency_rules, None)?;
+        let odds = probability::auto_odds(config.base_config.get_wagers(), house_edge)?;
+        config.set_odds(odds)?;
+        config.house_edge = Some(house_edge);
+        Ok(config)
+    }
+
+    // Replaces the odds, used once the wagers are known to be valid for pricing
+    pub fn set_odds(&mut self, odds: Odds) -> Result<()> {
+        let mut violations = odds.validate();
+        violations.append(Fts::validate_payouts(self.base_config.get_wagers(), &odds));
+        violations.into_result()?;
 
-        Ok( Fts{ base_config, odds: opt_odds.unwrap_or(Odds::default()) })
+        self.odds = odds;
+        Ok(())
+    }
+
+    fn validate_wager_types(wagers: &WagerMap<FtsWagerType>) -> Violations {
+        let mut violations = Violations::new();
+
+        for (player, wagers) in wagers {
+            for (i, wager) in wagers.iter().enumerate() {
+                violations.append(wager.get_wager_type().validate(pointer(&["wagers", player.get_id(), &i.to_string(), "wager_type"])));
+            }
+        }
+
+        violations
+    }
+
+    // Every payout, and the house line of each currency adding them up, must fit in Money whatever the outcome
+    fn validate_payouts(wagers: &WagerMap<FtsWagerType>, odds: &Odds) -> Violations {
+        let mut violations = Violations::new();
+        let mut house_payouts: BTreeMap<&Currency, Option<Money>> = BTreeMap::new();
+
+        for (player, wagers) in wagers {
+            for (i, wager) in wagers.iter().enumerate() {
+                // reported by validate_wager_types
+                if wager.get_wager_type().get_error().is_some() {
+                    continue;
+                }
+
+                let multiplier = wager.get_wager_type().get_max_multiplier(odds);
+
+                match wager.amount.checked_mul(multiplier) {
+                    Ok(payout) => {
+                        let house_payout = house_payouts.entry(wager.get_currency()).or_insert(Some(Money::ZERO));
+                        *house_payout = house_payout.and_then(|house_payout| house_payout.checked_add(payout).ok());
+                    },
+                    Err(_) => violations.add(
+                        pointer(&["wagers", player.get_id(), &i.to_string(), "amount"]),
+                        FtsConfigError::PayoutOverflow(wager.amount, multiplier)
+                    )
+                }
+            }
+        }
+
+        for (currency, _) in house_payouts.into_iter().filter(|(_, house_payout)| house_payout.is_none()) {
+            violations.add(pointer(&["wagers"]), FtsConfigError::HousePayoutOverflow(currency.clone()));
+        }
+
+        violations
     }
 
     pub fn get_base_config(&self) -> &Config<FtsWagerType> {
@@ -64,4 +290,151 @@ impl Fts {
     pub fn get_odds(&self) -> &Odds {
         &self.odds
     }
+
+    pub fn get_house_edge(&self) -> Option<f64> {
+        self.house_edge
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::fts::FtsWagerType::FullDeck;
+    use super::*;
+
+    fn wager_map() -> HashMap<Player, Vec<Wager<FtsWagerType>>> {
+        HashMap::from([(Player::new("player1".to_string()), vec![Wager::new(0, FullDeck, Money::from_minor(100)).unwrap()])])
+    }
+
+    #[test]
+    fn default_odds() -> Result<()> {
+        let config = Fts::new(wager_map(), "house".to_string(), CurrencyRules::default(), None)?;
+
+        assert_eq!(*config.get_odds().get_full_deck(), 17);
+        assert_eq!(*config.get_odds().get_at_flop(), 17);
+        assert_eq!(config.get_odds().get_flop_range_odds(0, 4), 17);
+        Ok(())
+    }
+
+    #[test]
+    fn custom_odds() -> Result<()> {
+        let odds: Odds = serde_json::from_str(r#"{ "full_deck": 20, "at_flop": 12, "flop_range": 9 }"#)?;
+        let config = Fts::new(wager_map(), "house".to_string(), CurrencyRules::default(), Some(odds))?;
+
+        assert_eq!(*config.get_odds().get_full_deck(), 20);
+        assert_eq!(*config.get_odds().get_at_flop(), 12);
+        assert_eq!(config.get_odds().get_flop_range_odds(0, 4), 9);
+        Ok(())
+    }
+
+    #[test]
+    fn out_of_bounds_odds() {
+        for (full_deck, at_flop, flop_range) in [(0, 17, 17), (17, -1, 17), (17, 17, MAX_ODDS + 1)] {
+            let odds = Odds { full_deck, at_flop, flop_range, flop_range_table: Vec::new() };
+            assert!(Fts::new(wager_map(), "house".to_string(), CurrencyRules::default(), Some(odds)).is_err());
+        }
+    }
+
+    #[test]
+    fn flop_range_paytable() -> Result<()> {
+        let odds: Odds = serde_json::from_str(r#"{
+            "full_deck": 17, "at_flop": 17, "flop_range": 15,
+            "flop_range_table": [
+                { "length": 1, "odds": 16 },
+                { "length": 5, "odds": 4 },
+                { "length": 5, "start": 0, "odds": 6 }
+            ]
+        }"#)?;
+        let config = Fts::new(wager_map(), "house".to_string(), CurrencyRules::default(), Some(odds))?;
+        let odds = config.get_odds();
+
+        assert_eq!(odds.get_flop_range_odds(3, 3), 16);
+        assert_eq!(odds.get_flop_range_odds(0, 4), 6);
+        assert_eq!(odds.get_flop_range_odds(2, 6), 4);
+        assert_eq!(odds.get_flop_range_odds(0, 9), 15);
+        Ok(())
+    }
+
+    #[test]
+    fn full_width_flop_range() {
+        assert_eq!(get_flop_range_length(0, u8::MAX), 256);
+
+        let mut odds = Odds::default();
+        odds.set_odds(&FtsWagerType::FlopRange(0, u8::MAX), 30);
+        assert_eq!(odds.get_flop_range_odds(0, u8::MAX), 30);
+        assert_eq!(odds.get_flop_range_odds(1, u8::MAX), 17);
+    }
+
+    #[test]
+    fn invalid_flop_range_paytable() {
+        for table in [
+            r#"[{ "length": 0, "odds": 10 }]"#,
+            r#"[{ "length": 2, "odds": 0 }]"#,
+            r#"[{ "length": 2, "start": 1, "odds": 10 }, { "length": 2, "start": 1, "odds": 12 }]"#
+        ] {
+            let odds: Odds = serde_json::from_str(&format!(
+                r#"{{ "full_deck": 17, "at_flop": 17, "flop_range": 17, "flop_range_table": {} }}"#, table
+            )).unwrap();
+            assert!(Fts::new(wager_map(), "house".to_string(), CurrencyRules::default(), Some(odds)).is_err());
+        }
+    }
+
+    #[test]
+    fn every_violation_is_reported() {
+        let wagers: Vec<Wager<FtsWagerType>> = serde_json::from_str(r#"[
+            { "id": 0, "wager_type": { "FlopRange": [5, 2] }, "amount": 10 },
+            { "id": 0, "wager_type": { "AtFlop": 40 }, "amount": 0 }
+        ]"#).unwrap();
+        let odds = Odds { full_deck: 0, ..Odds::default() };
+
+        let error = Fts::new(HashMap::from([(Player::new("player1".to_string()), wagers)]), String::new(), CurrencyRules::default(), Some(odds))
+            .err().unwrap();
+        let paths: Vec<String> = error.downcast::<crate::validation::ValidationErrors>().unwrap().0.into_iter()
+            .map(|violation| violation.path)
+            .collect();
+
+        assert_eq!(paths, vec![
+            "/house_id",
+            "/odds/full_deck",
+            "/wagers/player1/0/wager_type",
+            "/wagers/player1/1/amount",
+            "/wagers/player1/1/id",
+            "/wagers/player1/1/wager_type"
+        ]);
+    }
+
+    #[test]
+    fn payout_overflow_is_a_violation() {
+        let paths = |amounts: &[i64]| -> Vec<String> {
+            let wagers = amounts.iter().enumerate()
+                .map(|(i, amount)| Wager::new(i as u32, FullDeck, Money::from_minor(*amount)).unwrap())
+                .collect();
+            let error = Fts::new(HashMap::from([(Player::new("player1".to_string()), wagers)]), "house".to_string(), CurrencyRules::default(), None)
+                .err().unwrap();
+
+            error.downcast::<crate::validation::ValidationErrors>().unwrap().0.into_iter()
+                .map(|violation| violation.path)
+                .collect()
+        };
+
+        // a full deck wager can lose 17 times its amount
+        assert_eq!(paths(&[i64::MAX / 10]), vec!["/wagers/player1/0/amount"]);
+        assert_eq!(paths(&[i64::MAX / 20, i64::MAX / 20]), vec!["/wagers"]);
+    }
+
+    #[test]
+    fn invalid_wager_types_are_not_paid() {
+        for wager_type in [r#"{ "FlopRange": [0, 255] }"#, r#"{ "FlopRange": [20, 30] }"#, r#"{ "AtFlop": 200 }"#] {
+            let wagers: Vec<Wager<FtsWagerType>> = serde_json::from_str(&format!(
+                r#"[{{ "id": 0, "wager_type": {}, "amount": {} }}]"#, wager_type, i64::MAX / 2
+            )).unwrap();
+
+            let error = Fts::new(HashMap::from([(Player::new("player1".to_string()), wagers)]), "house".to_string(), CurrencyRules::default(), None)
+                .err().unwrap();
+            let paths: Vec<String> = error.downcast::<crate::validation::ValidationErrors>().unwrap().0.into_iter()
+                .map(|violation| violation.path)
+                .collect();
+
+            assert_eq!(paths, vec!["/wagers/player1/0/wager_type"]);
+        }
+    }
 }
\ No newline at end of file
@@ -1,7 +1,17 @@
 use crate::card::{Card, get_suit_count, get_rank_count};
-use rand::thread_rng;
-use rand::seq::SliceRandom;
+use crate::shuffler::Shuffler;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("Cannot split a deck of {0} card(s)")]
+    SplitTooSmall(usize),
+    #[error("Split index {0} must leave cards after it in a deck of {1} card(s)")]
+    SplitIndex(usize, usize)
+}
 
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct Deck {
     cards: Vec<Card>,
     next_idx: usize
@@ -45,25 +55,23 @@ impl Deck {
 
     // Returns the newly split off deck: (index, len-1)
     //TODO: implement split interval: startIdx & endIdx
-    pub fn split(&mut self, index: usize) -> Self {
+    pub fn split(&mut self, index: usize) -> Result<Self, Error> {
         if self.cards.len() <= 1 {
-            //TODO: error here
-            panic!()
+            return Err(Error::SplitTooSmall(self.cards.len()));
         }
 
         if index >= self.cards.len() - 1 {
-            //TODO: error here
-            panic!()
+            return Err(Error::SplitIndex(index, self.cards.len()));
         }
 
-        Deck {
+        Ok(Deck {
             cards: self.cards.split_off(index+ 1),
             next_idx: 0
-        }
+        })
     }
 
-    pub fn shuffle(&mut self) {
-        self.cards.shuffle(&mut thread_rng());
+    pub fn shuffle(&mut self, shuffler: &mut Shuffler) {
+        shuffler.shuffle(&mut self.cards);
 
         //shuffling serves to reset the deck
         self.next_idx = 0;
@@ -94,6 +102,10 @@ impl Deck {
         &self.cards[0..self.next_idx]
     }
 
+    pub fn get_cards(&self) -> &[Card] {
+        &self.cards
+    }
+
     pub fn len(&self) -> usize {
         self.cards.len()
     }
@@ -104,6 +116,12 @@ mod tests {
     use crate::card::Suit::Hearts;
     use super::*;
 
+    impl Deck {
+        pub fn from_cards(cards: Vec<Card>) -> Self {
+            Deck { cards, next_idx: 0 }
+        }
+    }
+
     #[test]
     fn create_empty_deck() {
         let deck = Deck::new();
@@ -158,7 +176,7 @@ mod tests {
             next_idx: 0
         };
 
-        let new_deck = orig_deck.split(0);
+        let new_deck = orig_deck.split(0).unwrap();
 
         assert_eq!(orig_deck.cards.len(), 1);
         assert_eq!(orig_deck.cards[0], Card::new(1, Hearts).unwrap());
@@ -182,7 +200,7 @@ mod tests {
             next_idx: 0
         };
 
-        let new_deck = orig_deck.split(2);
+        let new_deck = orig_deck.split(2).unwrap();
 
         assert_eq!(orig_deck.cards.len(), 3);
 
@@ -208,7 +226,7 @@ mod tests {
             next_idx: 0
         };
 
-        let new_deck = orig_deck.split(1);
+        let new_deck = orig_deck.split(1).unwrap();
 
         assert_eq!(orig_deck.cards.len(), 2);
         assert_eq!(orig_deck.cards[0], Card::new(1, Hearts).unwrap());
@@ -222,6 +240,14 @@ mod tests {
     #[test]
     #[should_panic]
     fn invalid_split() {
-        todo!()
+        Deck::default().split(51).unwrap();
+    }
+
+    #[test]
+    fn invalid_split_errors() {
+        assert_eq!(Deck::new().split(0).err(), Some(Error::SplitTooSmall(0)));
+        assert_eq!(Deck::from_cards(vec![Card::new(1, Hearts).unwrap()]).split(0).err(), Some(Error::SplitTooSmall(1)));
+        assert_eq!(Deck::default().split(51).err(), Some(Error::SplitIndex(51, 52)));
+        assert_eq!(Deck::default().split(100).err(), Some(Error::SplitIndex(100, 52)));
     }
 }
\ No newline at end of file
@@ -1,11 +1,186 @@
-use std::collections::HashMap;
-use serde::Deserialize;
-use crate::player::Player;
-use crate::wager::Wager;
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::card::Card;
+use crate::config;
+use crate::config::fts::{FtsWagerType, Odds};
+use crate::game::cta::Outcome;
+use crate::ledger::{Account, StatementLine};
+use crate::money::{Currency, Money};
+use crate::state::State;
+use crate::validation::{pointer, Violations};
+
+// Game specific settings, read from the same object as the wagers. `validate` checks what serde cannot.
+pub trait SettingsDto {
+    fn validate(&self, _violations: &mut Violations) {}
+}
+
+#[derive(Deserialize)]
+pub struct FtsSettingsDto {
+    pub odds: Option<OddsDto>,
+    pub house_edge: Option<f64>     // target edge when the odds are auto priced
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum OddsDto {
+    Auto(AutoOdds),
+    Explicit(Odds)
+}
 
 #[derive(Deserialize)]
-pub struct ConfigDto<T> {
-    #[serde(rename = "wagers")]
-    pub wager_map: HashMap<Player, Vec<Wager<T>>>,
-    pub house_id: Option<String>
+pub enum AutoOdds {
+    #[serde(rename = "auto")]
+    Auto
+}
+
+impl SettingsDto for FtsSettingsDto {
+    fn validate(&self, violations: &mut Violations) {
+        if matches!(self.odds, Some(OddsDto::Auto(_))) && self.house_edge.is_none() {
+            violations.add(pointer(&["house_edge"]), "Auto odds need a target house edge");
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FtsPricingDto {
+    pub wager_type: FtsWagerType,
+    pub house_edge: f64
+}
+
+#[derive(Serialize)]
+pub struct PricingDto {
+    pub odds: i32,
+    pub house_edge: f64
+}
+
+#[derive(Deserialize)]
+pub struct CtaSettingsDto {
+    pub cuts: Option<u8>
+}
+
+impl SettingsDto for CtaSettingsDto {}
+
+#[derive(Serialize)]
+pub struct GameStateDto<'a> {
+    pub state: &'a State,
+    pub deck_pool: Vec<DeckLayoutDto>
+}
+
+#[derive(Serialize)]
+pub struct ErrorDto {
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<serde_json::Value>
+}
+
+// Odds of a single wager, `expected_payout` and `max_loss` are for the whole wager amount
+#[derive(Serialize)]
+pub struct WagerOddsDto<'a> {
+    pub player_id: &'a str,
+    pub wager_id: u32,
+    pub currency: &'a Currency,
+    pub win_probability: f64,
+    pub expected_payout: f64,
+    pub max_loss: f64,
+    pub house_edge: f64
+}
+
+// Everything needed to review a game, results are only filled in once it has ended
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+pub enum GameDto<'a> {
+    Fts {
+        state: &'a State,
+        config: &'a config::Fts,
+        #[serde(flatten)]
+        result: Option<FtsResultDto<'a>>
+    },
+    Cta {
+        state: &'a State,
+        config: &'a config::Cta,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        outcome: Option<Outcome>
+    }
+}
+
+#[derive(Serialize)]
+pub struct FtsResultDto<'a> {
+    pub flops: Vec<&'a [Card]>,
+    pub flopped_at: Option<u8>
+}
+
+#[derive(Serialize)]
+pub struct DeckLayoutDto {
+    pub cards: usize,
+    pub dealt: usize
+}
+
+// Client seeds are read from the game configuration next to the wagers, keyed by player id
+// The wager is read as the game type reads its wagers
+#[derive(Deserialize)]
+pub struct PlaceWagerDto {
+    pub player_id: String,
+    pub wager: serde_json::Value
+}
+
+#[derive(Deserialize)]
+pub struct AmendWagerDto {
+    pub amount: Money
+}
+
+#[derive(Deserialize)]
+pub struct ClientSeedsDto {
+    #[serde(default)]
+    pub client_seeds: BTreeMap<String, String>
+}
+
+// Seeds are hex encoded, the server and shuffle seeds are only given out once the game has ended
+#[derive(Serialize)]
+pub struct FairnessDto<'a> {
+    pub commitment: String,
+    pub client_seeds: &'a BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_seed: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shuffle_seed: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct VerifyDto {
+    pub server_seed: String,
+    #[serde(default)]
+    pub client_seeds: BTreeMap<String, String>,
+    pub flops: Option<u8>           // number of flops dealt, a full deck when not set
+}
+
+#[derive(Serialize)]
+pub struct VerificationDto {
+    pub commitment: String,
+    pub shuffle_seed: String,
+    pub deck: Vec<String>,
+    pub flopped_at: Option<u8>
+}
+
+// Money moved between a wallet and the outside world
+#[derive(Deserialize)]
+pub struct TransferDto {
+    pub currency: Currency,
+    pub amount: Money
+}
+
+#[derive(Serialize)]
+pub struct EntryDto {
+    pub entry_id: u64
+}
+
+#[derive(Serialize)]
+pub struct BalanceDto<'a> {
+    pub account: &'a Account,
+    pub balances: BTreeMap<Currency, Money>
+}
+
+#[derive(Serialize)]
+pub struct StatementDto<'a> {
+    pub account: &'a Account,
+    pub lines: Vec<StatementLine>
 }
\ No newline at end of file
@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use serde::Serialize;
+use crate::game;
+use crate::game::Error::{NotFound, StorageFull};
+use crate::game::Snapshot;
+use crate::game_storage::{GameStorage, StoredGame};
+use crate::payout::Payout;
+use crate::state::GameState::{Ended, Setup, Voided};
+use crate::state::State;
+
+pub struct ExpiryPolicy {
+    pub setup_ttl: Duration,        // games never started are dropped after this long without a transition
+    pub ended_ttl: Duration,        // finished games are evicted this long after they ended
+    pub max_games: usize,
+    pub interval: Duration          // time between two sweeps of the background task
+}
+
+impl Default for ExpiryPolicy {
+    fn default() -> Self {
+        ExpiryPolicy {
+            setup_ttl: Duration::from_secs(60 * 60),
+            ended_ttl: Duration::from_secs(24 * 60 * 60),
+            max_games: 100_000,
+            interval: Duration::from_secs(60)
+        }
+    }
+}
+
+// Only games that are not being played can be evicted, finished ones go first
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Evictable {
+    Ended,
+    Setup
+}
+
+impl Evictable {
+    fn of(state: &State) -> Option<Self> {
+        match state {
+            State::Game(Ended | Voided { .. }) => Some(Evictable::Ended),
+            State::Game(Setup) => Some(Evictable::Setup),
+            _ => None
+        }
+    }
+}
+
+// Settles or releases what the ledger holds for a game about to be evicted
+pub(crate) type CloseBooks<'a> = dyn Fn(&str, &StoredGame) -> Result<()> + 'a;
+
+// Everything needed to restore or audit an evicted game
+#[derive(Serialize)]
+struct ArchivedGame<'a> {
+    id: &'a str,
+    snapshot: Snapshot,
+    server_seed: String,
+    client_seeds: &'a BTreeMap<String, String>,
+    payouts: Vec<Payout<'a>>
+}
+
+// Removes expired games and keeps the storage under its cap. Evicted finished games are appended to the archive
+// file when there is one, a game is only removed once it has been archived. The books of a game are closed
+// under its lock right before it goes, a game whose books cannot be closed is kept.
+pub struct Evictor {
+    policy: ExpiryPolicy,
+    archive: Option<Mutex<File>>,
+    admitting: AtomicUsize // games being created, they count against the cap until they are stored
+}
+
+// Releases the slot of an admitted game once it is stored, or when its creation failed
+struct Admission<'a>(&'a AtomicUsize);
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Default for Evictor {
+    fn default() -> Self {
+        Evictor::new(ExpiryPolicy::default())
+    }
+}
+
+impl Evictor {
+    pub fn new(policy: ExpiryPolicy) -> Self {
+        Evictor { policy, archive: None, admitting: AtomicUsize::new(0) }
+    }
+
+    pub fn with_archive(policy: ExpiryPolicy, path: impl AsRef<Path>) -> Result<Self> {
+        let archive = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Evictor { policy, archive: Some(Mutex::new(archive)), admitting: AtomicUsize::new(0) })
+    }
+
+    pub fn get_interval(&self) -> Duration {
+        self.policy.interval
+    }
+
+    // Creates a game through `create` once there is room for it. At the cap the least recently touched finished
+    // game is evicted, then the least recently touched unstarted one, games being played are never evicted.
+    // Admissions run side by side, each one holds a slot while its game is created so the cap still holds.
+    pub(crate) fn admit(&self, storage: &dyn GameStorage, close_books: &CloseBooks<'_>, create: impl FnOnce() -> Result<String>) -> Result<String> {
+        self.admitting.fetch_add(1, Ordering::SeqCst);
+        let _admission = Admission(&self.admitting);
+        let mut candidates = None;
+
+        while storage.game_count() + self.admitting.load(Ordering::SeqCst) > self.policy.max_games {
+            // the games are looked at once per admission, then tried from the most evictable on
+            let candidates = candidates.get_or_insert_with(|| Evictor::candidates(storage));
+
+            let Some((evictable, touched_at, id)) = candidates.pop() else {
+                return Err(StorageFull(self.policy.max_games).into());
+            };
+
+            // the game may have moved on since it was picked, it is then left alone and the next one is tried
+            let removed = storage.remove_game_if(&id, &mut |stored| {
+                let unchanged = Evictable::of(stored.get_game().get_state()).as_ref() == Some(&evictable)
+                    && stored.get_touched_at() == touched_at;
+
+                if !unchanged {
+                    return Ok(false);
+                }
+
+                self.release(&id, stored, close_books)
+            });
+
+            removed_unless_gone(removed)?;
+        }
+
+        create()
+    }
+
+    // Evictable games, the most evictable last. Games locked right now are in use and skipped.
+    fn candidates(storage: &dyn GameStorage) -> Vec<(Evictable, Instant, String)> {
+        let mut candidates: Vec<_> = storage.list_games()
+            .into_iter()
+            .filter_map(|(id, game_ref)| {
+                let stored = game_ref.try_lock().ok()?;
+                Evictable::of(stored.get_game().get_state()).map(|evictable| (evictable, stored.get_touched_at(), id))
+            })
+            .collect();
+
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+        candidates
+    }
+
+    // Removes every game past its TTL, returns how many were evicted
+    pub(crate) fn evict_expired(&self, storage: &dyn GameStorage, close_books: &CloseBooks<'_>) -> Result<usize> {
+        let now = Instant::now();
+        let mut evicted = 0;
+
+        for (id, _) in storage.list_games() {
+            let removed = storage.remove_game_if(&id, &mut |stored| {
+                let ttl = match Evictable::of(stored.get_game().get_state()) {
+                    Some(Evictable::Ended) => self.policy.ended_ttl,
+                    Some(Evictable::Setup) => self.policy.setup_ttl,
+                    None => return Ok(false)
+                };
+
+                if now.saturating_duration_since(stored.get_touched_at()) < ttl {
+                    return Ok(false);
+                }
+
+                self.release(&id, stored, close_books)
+            });
+
+            if removed_unless_gone(removed)? {
+                evicted += 1;
+            }
+        }
+
+        Ok(evicted)
+    }
+
+    fn release(&self, id: &str, stored: &StoredGame, close_books: &CloseBooks<'_>) -> Result<bool> {
+        if let Err(e) = close_books(id, stored) {
+            eprintln!("Game {} kept, its books could not be closed: {}", id, e);
+            return Ok(false);
+        }
+
+        if let Err(e) = self.archive(id, stored) {
+            eprintln!("Game {} kept, it could not be archived: {}", id, e);
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    fn archive(&self, id: &str, stored: &StoredGame) -> Result<()> {
+        let Some(archive) = &self.archive else {
+            return Ok(());
+        };
+
+        if !stored.get_game().get_state().is_over() {
+            return Ok(());
+        }
+
+        let archived = ArchivedGame {
+            id,
+            snapshot: stored.get_game().snapshot()?,
+            server_seed: hex::encode(stored.get_fairness().get_server_seed()),
+            client_seeds: stored.get_fairness().get_client_seeds(),
+            payouts: stored.get_game().get_payout()?
+        };
+
+        let mut archive = archive.lock().unwrap();
+        writeln!(archive, "{}", serde_json::to_string(&archived)?)?;
+        archive.sync_data()?;
+        Ok(())
+    }
+}
+
+// A game removed by someone else in the meantime counts as not evicted
+fn removed_unless_gone(removed: Result<bool>) -> Result<bool> {
+    match removed {
+        Err(e) if matches!(e.downcast_ref::<game::Error>(), Some(NotFound(_))) => Ok(false),
+        removed => removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::fairness::Fairness;
+    use crate::game::GameType;
+    use crate::game_storage::MemoryStorage;
+    use crate::transition::GameTransition::Start;
+    use crate::transition::Transition;
+    use super::*;
+
+    const FTS_CONFIG: &str = r#"{ "wagers": { "player1": [{ "id": 0, "wager_type": "FullDeck", "amount": 10 }] }, "house_id": "house" }"#;
+    const CTA_CONFIG: &str = r#"{ "wagers": { "player1": [{ "id": 0, "wager_type": "Forward", "amount": 10 }] }, "house_id": "house" }"#;
+
+    fn policy(ttl: Duration, max_games: usize) -> ExpiryPolicy {
+        ExpiryPolicy { setup_ttl: ttl, ended_ttl: ttl, max_games, interval: Duration::from_secs(1) }
+    }
+
+    fn create(storage: &MemoryStorage, evictor: &Evictor, game_type: GameType, start: bool) -> Result<String> {
+        let config = match game_type {
+            GameType::Fts => FTS_CONFIG,
+            GameType::Cta => CTA_CONFIG
+        };
+
+        let id = evictor.admit(storage, &|_, _| Ok(()), || storage.create_game(game_type, config, Fairness::new()))?;
+
+        if start {
+            storage.transition_game(&id, Transition::Game(Start))?;
+        }
+
+        Ok(id)
+    }
+
+    #[test]
+    fn expired_games_are_evicted() -> Result<()> {
+        let archive_path = std::env::temp_dir().join(format!("zsonkorp-archive-{}.jsonl", uuid::Uuid::new_v4()));
+        let storage = MemoryStorage::new();
+        let evictor = Evictor::with_archive(policy(Duration::ZERO, 10), &archive_path)?;
+
+        create(&storage, &evictor, GameType::Fts, false)?;
+        let ended_id = create(&storage, &evictor, GameType::Fts, true)?;
+        let playing_id = create(&storage, &evictor, GameType::Cta, true)?;
+
+        let evicted = evictor.evict_expired(&storage, &|_, _| Ok(()))?;
+        let archive = fs::read_to_string(&archive_path)?;
+        fs::remove_file(&archive_path)?;
+
+        assert_eq!(evicted, 2);
+        assert_eq!(storage.list_games().into_iter().map(|(id, _)| id).collect::<Vec<String>>(), vec![playing_id]);
+
+        // only the finished game is archived, with what is needed to restore it
+        let archived: Vec<serde_json::Value> = archive.lines().map(serde_json::from_str).collect::<Result<_, _>>()?;
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0]["id"], ended_id.as_str());
+        assert!(crate::game::restore(serde_json::from_value(archived[0]["snapshot"].clone())?).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn fresh_games_are_kept() -> Result<()> {
+        let storage = MemoryStorage::new();
+        let evictor = Evictor::new(policy(Duration::from_secs(60 * 60), 10));
+
+        create(&storage, &evictor, GameType::Fts, false)?;
+        create(&storage, &evictor, GameType::Fts, true)?;
+
+        assert_eq!(evictor.evict_expired(&storage, &|_, _| Ok(()))?, 0);
+        assert_eq!(storage.game_count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn capacity_eviction_order() -> Result<()> {
+        let storage = MemoryStorage::new();
+        let evictor = Evictor::new(policy(Duration::from_secs(60 * 60), 3));
+
+        let setup_id = create(&storage, &evictor, GameType::Fts, false)?;
+        let ended_id = create(&storage, &evictor, GameType::Fts, true)?;
+        let playing_id = create(&storage, &evictor, GameType::Cta, true)?;
+
+        // finished games go before unstarted ones, even when touched later
+        let newer_id = create(&storage, &evictor, GameType::Cta, true)?;
+        assert!(storage.get_game(&ended_id).is_none());
+        assert!(storage.get_game(&setup_id).is_some());
+
+        let newest_id = create(&storage, &evictor, GameType::Cta, true)?;
+        assert!(storage.get_game(&setup_id).is_none());
+
+        // games being played are never evicted
+        let full = create(&storage, &evictor, GameType::Fts, false);
+        assert!(matches!(full.unwrap_err().downcast_ref::<game::Error>(), Some(StorageFull(3))));
+
+        for id in [playing_id, newer_id, newest_id] {
+            assert!(storage.get_game(&id).is_some());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn games_with_open_books_are_kept() -> Result<()> {
+        let storage = MemoryStorage::new();
+        let evictor = Evictor::new(policy(Duration::ZERO, 1));
+        let unsettled: &CloseBooks = &|_, _| Err(anyhow::anyhow!("ledger unavailable"));
+
+        let id = create(&storage, &evictor, GameType::Fts, true)?;
+
+        assert_eq!(evictor.evict_expired(&storage, unsettled)?, 0);
+        assert!(matches!(evictor.admit(&storage, unsettled, || Ok(String::new())).unwrap_err().downcast_ref::<game::Error>(), Some(StorageFull(1))));
+        assert!(storage.get_game(&id).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn unarchived_games_are_kept() -> Result<()> {
+        let archive_path = std::env::temp_dir().join(format!("zsonkorp-archive-{}.jsonl", uuid::Uuid::new_v4()));
+        fs::write(&archive_path, "")?;
+
+        // an archive opened for reading refuses every line
+        let storage = MemoryStorage::new();
+        let evictor = Evictor { archive: Some(Mutex::new(File::open(&archive_path)?)), ..Evictor::new(policy(Duration::ZERO, 10)) };
+
+        let ended_id = create(&storage, &evictor, GameType::Fts, true)?;
+        let setup_id = create(&storage, &evictor, GameType::Fts, false)?;
+
+        // the sweep goes on past the game it could not archive
+        let evicted = evictor.evict_expired(&storage, &|_, _| Ok(()));
+        fs::remove_file(&archive_path)?;
+
+        assert_eq!(evicted?, 1);
+        assert!(storage.get_game(&ended_id).is_some());
+        assert!(storage.get_game(&setup_id).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn failed_creation_frees_its_slot() -> Result<()> {
+        let storage = MemoryStorage::new();
+        let evictor = Evictor::new(policy(Duration::from_secs(60 * 60), 1));
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            evictor.admit(&storage, &|_, _| Ok(()), || panic!("creation failed"))
+        }));
+        assert!(panicked.is_err());
+        assert!(evictor.admit(&storage, &|_, _| Ok(()), || Err(StorageFull(0).into())).is_err());
+
+        create(&storage, &evictor, GameType::Cta, true)?;
+        assert_eq!(storage.game_count(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use anyhow::{anyhow, Result};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use crate::shuffler::{Seed, Shuffler};
+
+// Commit-reveal record of a game. The server seed is drawn when the game is created and only its SHA-256
+// commitment is given out until the game has ended. Players add client seeds once the commitment is published,
+// the shuffle seed is SHA-256(server seed bytes || JSON of the client seeds keyed and sorted by player id), so
+// neither side alone decides the deck order.
+pub struct Fairness {
+    server_seed: Seed,
+    client_seeds: BTreeMap<String, String>
+}
+
+impl Default for Fairness {
+    fn default() -> Self {
+        Fairness::new()
+    }
+}
+
+impl Fairness {
+    // Client seeds cannot be given yet, they come after the commitment
+    pub fn new() -> Self {
+        let mut server_seed = Seed::default();
+        OsRng.fill_bytes(&mut server_seed);
+
+        Fairness { server_seed, client_seeds: BTreeMap::new() }
+    }
+
+    // Same server seed with more client seeds, a player submitting again replaces their seed
+    pub fn with_client_seeds(&self, client_seeds: BTreeMap<String, String>) -> Self {
+        let mut merged = self.client_seeds.clone();
+        merged.extend(client_seeds);
+
+        Fairness { server_seed: self.server_seed, client_seeds: merged }
+    }
+
+    // Rebuilds the record from a revealed server seed, given hex encoded
+    pub fn from_revealed(server_seed: &str, client_seeds: BTreeMap<String, String>) -> Result<Self> {
+        let server_seed = Seed::try_from(hex::decode(server_seed)?.as_slice())
+            .map_err(|_| anyhow!("Server seed must be {} bytes", Seed::default().len()))?;
+
+        Ok(Fairness { server_seed, client_seeds })
+    }
+
+    pub fn get_server_seed(&self) -> &Seed {
+        &self.server_seed
+    }
+
+    pub fn get_client_seeds(&self) -> &BTreeMap<String, String> {
+        &self.client_seeds
+    }
+
+    pub fn get_commitment(&self) -> Seed {
+        Sha256::digest(self.server_seed).into()
+    }
+
+    pub fn get_shuffle_seed(&self) -> Seed {
+        let mut hasher = Sha256::new();
+        hasher.update(self.server_seed);
+        hasher.update(serde_json::to_vec(&self.client_seeds).unwrap());
+        hasher.finalize().into()
+    }
+
+    pub fn get_shuffler(&self) -> Shuffler {
+        Shuffler::from_seed(self.get_shuffle_seed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_seeds() -> BTreeMap<String, String> {
+        BTreeMap::from([("player1".to_string(), "lucky".to_string()), ("player2".to_string(), "7".to_string())])
+    }
+
+    #[test]
+    fn commitment_matches_revealed_seed() -> Result<()> {
+        let fairness = Fairness::new().with_client_seeds(client_seeds());
+        let revealed = Fairness::from_revealed(&hex::encode(fairness.get_server_seed()), client_seeds())?;
+
+        assert_eq!(revealed.get_commitment(), fairness.get_commitment());
+        assert_eq!(revealed.get_shuffle_seed(), fairness.get_shuffle_seed());
+        assert_ne!(fairness.get_commitment(), *fairness.get_server_seed());
+        Ok(())
+    }
+
+    #[test]
+    fn client_seeds_change_the_shuffle() -> Result<()> {
+        let server_seed = hex::encode([3; 32]);
+        let fairness = Fairness::from_revealed(&server_seed, client_seeds())?;
+
+        let mut other_seeds = client_seeds();
+        other_seeds.insert("player2".to_string(), "8".to_string());
+
+        assert_ne!(Fairness::from_revealed(&server_seed, other_seeds)?.get_shuffle_seed(), fairness.get_shuffle_seed());
+        assert_ne!(Fairness::from_revealed(&server_seed, BTreeMap::new())?.get_shuffle_seed(), fairness.get_shuffle_seed());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_server_seed() {
+        assert!(Fairness::from_revealed("not hex", BTreeMap::new()).is_err());
+        assert!(Fairness::from_revealed(&hex::encode([1; 16]), BTreeMap::new()).is_err());
+    }
+}
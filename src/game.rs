@@ -1,66 +1,280 @@
+use std::collections::BTreeMap;
 use thiserror::Error;
 use anyhow::{anyhow, Result};
 use axum::response::IntoResponse;
-use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use crate::config;
-use crate::config::FtsWagerType;
+use crate::config::{CtaWagerType, CurrencyRules, FtsWagerType};
 
 pub(crate) mod fts;
-mod cta;
+pub(crate) mod cta;
+#[cfg(test)]
+mod test_util;
 
-use crate::dto::ConfigDto;
+use crate::deck::Deck;
+use crate::dto::{CtaSettingsDto, FtsSettingsDto, GameDto, OddsDto, SettingsDto, WagerOddsDto};
+use crate::game::cta::Cta;
 use crate::game::fts::Fts;
+use crate::money::{Currency, Money};
 use crate::payout::Payout;
+use crate::player::Player;
+use crate::shuffler::{Seed, Shuffler};
+use crate::state::GameState::Voided;
+use crate::state::State;
 use crate::transition::Transition;
+use crate::validation::{pointer, ValidationErrors, Violations};
+use crate::wager::Wager;
 
 #[derive(Error, Debug)]
 pub(crate) enum Error {
     #[error("Generic game error")]
     Generic,
-    #[error("Error parsing game configuration: {0}")]
-    ParseConfig(String),
-    #[error("Unknown game type")]
-    UnknownGame,
+    #[error("{0}")]
+    Validation(String),
     #[error("Invalid transition")]
     InvalidTransition,
+    #[error("Transition rejected: {0}")]
+    RejectedTransition(String),
     #[error("Game not found: {0}")]
-    NotFound(String)
+    NotFound(String),
+    #[error("Game storage is full, at most {0} games are kept")]
+    StorageFull(usize),
+    #[error("Wagers can only change before the game starts")]
+    BettingClosed,
+    #[error("Wager not found: {0}")]
+    WagerNotFound(u32),
+    #[error("Client seeds are only accepted before the game starts")]
+    SeedingClosed
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub enum GameType {
     Fts, Cta
 }
 
 pub trait Game: Sync + Send {
     fn get_type(&self) -> GameType;
+    fn get_state(&self) -> &State;
+    fn get_deck_pool(&self) -> &[Deck];
+    fn get_seed(&self) -> &Seed;
     fn transition(&mut self, transition: Transition) -> Result<()>;
     fn get_valid_transitions(&self) -> Vec<Transition>;
-    fn get_payout(&self) -> Result<Vec<Payout>>;
+    // Client seeds change the shuffler, which is only used once the game starts
+    fn reseed(&mut self, shuffler: Shuffler) -> Result<()>;
+    // Only games in setup take wager changes, the whole config is validated again
+    fn change_wagers(&mut self, change: WagerChange) -> Result<()>;
+    fn get_payout(&self) -> Result<Vec<Payout<'_>>>;
+    // Worst payout of every wager over all outcomes, which is what the wallets must cover
+    fn get_max_losses(&self) -> Result<Vec<Payout<'_>>>;
+    // Worst house line of every currency over all outcomes
+    fn get_house_max_losses(&self) -> Result<Vec<Payout<'_>>>;
+    fn get_details(&self) -> GameDto<'_>;
+    fn get_wager_odds(&self) -> Vec<WagerOddsDto<'_>>;
+    fn snapshot(&self) -> Result<Snapshot>;
 }
 
-pub fn create_game(game_type: &GameType, payload: &str) -> Result<Box<dyn Game>> {
+// A change to the wagers of a game in setup, wager ids are unique within a game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WagerChange {
+    // Players join the game with their first wager and leave it with their last one
+    Place { player_id: String, wager: serde_json::Value },
+    Amend { wager_id: u32, amount: Money },
+    Cancel { wager_id: u32 }
+}
+
+// Bumped whenever the serialized form of a game changes, older snapshots are refused rather than misread
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+// Complete serialized game, including its deck order and shuffler position
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    kind: GameType,
+    game: serde_json::Value
+}
+
+impl Snapshot {
+    fn of<G: Game + Serialize>(game: &G) -> Result<Self> {
+        Ok(Snapshot { version: SNAPSHOT_VERSION, kind: game.get_type(), game: serde_json::to_value(game)? })
+    }
+}
+
+pub fn restore(snapshot: Snapshot) -> Result<Box<dyn Game>> {
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(anyhow!("Unsupported snapshot version {}, expected {}", snapshot.version, SNAPSHOT_VERSION));
+    }
+
+    match snapshot.kind {
+        GameType::Fts => Ok(Box::new(serde_json::from_value::<Fts>(snapshot.game)?)),
+        GameType::Cta => Ok(Box::new(serde_json::from_value::<Cta>(snapshot.game)?))
+    }
+}
+
+// Lowest negative house line of every currency, out of the payouts of each possible outcome
+pub(crate) fn house_max_losses(outcomes: Vec<Vec<Payout<'_>>>) -> Vec<Payout<'_>> {
+    let mut max_losses: BTreeMap<&Currency, Payout> = BTreeMap::new();
+
+    for payout in outcomes.into_iter().flatten() {
+        if payout.get_wager_id().is_some() || payout.get_amount() >= Money::ZERO {
+            continue;
+        }
+
+        match max_losses.get(payout.get_currency()) {
+            Some(max_loss) if max_loss.get_amount() <= payout.get_amount() => (),
+            _ => {
+                max_losses.insert(payout.get_currency(), payout);
+            }
+        }
+    }
+
+    max_losses.into_values().collect()
+}
+
+// Voids a game that is not over yet, whatever its own state machine is at
+pub(crate) fn void(state: &State, reason: &str) -> Result<State> {
+    if state.is_over() {
+        return Err(Error::InvalidTransition.into());
+    }
+
+    if reason.trim().is_empty() {
+        return Err(Error::Validation("A reason is required to void a game".to_string()).into());
+    }
+
+    Ok(State::Game(Voided { reason: reason.to_string() }))
+}
+
+// Wagers once `change` is applied, the game config still has to accept them
+pub(crate) fn change_wagers<T>(wagers: &config::WagerMap<T>, change: WagerChange) -> Result<config::WagerMap<T>>
+    where T: DeserializeOwned + Clone {
+
+    let mut wagers = wagers.clone();
+
+    match change {
+        WagerChange::Place { player_id, wager } => {
+            let mut violations = Violations::new();
+            let wager = violations.deserialize::<Wager<T>>(&pointer(&["wager"]), &wager);
+            violations.into_result()?;
+
+            wagers.entry(Player::new(player_id)).or_default().extend(wager);
+        },
+        WagerChange::Amend { wager_id, amount } => {
+            let wager = wagers.values_mut()
+                .flatten()
+                .find(|wager| *wager.get_id() == wager_id)
+                .ok_or(Error::WagerNotFound(wager_id))?;
+
+            wager.amount = amount;
+        },
+        WagerChange::Cancel { wager_id } => {
+            let count: usize = wagers.values().map(Vec::len).sum();
+
+            for player_wagers in wagers.values_mut() {
+                player_wagers.retain(|wager| *wager.get_id() != wager_id);
+            }
+
+            if wagers.values().map(Vec::len).sum::<usize>() == count {
+                return Err(Error::WagerNotFound(wager_id).into());
+            }
+
+            wagers.retain(|_, player_wagers| !player_wagers.is_empty());
+        }
+    }
+
+    Ok(wagers)
+}
+
+// Zero-net payout of every wager of a voided game
+pub(crate) fn refunds<T>(wagers: &config::WagerMap<T>) -> Vec<Payout<'_>> {
+    wagers.iter()
+        .flat_map(|(player, wagers)| wagers.iter().map(move |wager| Payout::refund(player.get_id(), wager)))
+        .collect()
+}
+
+// Game, validation and serde errors are kept as they are, anything else becomes `wrap` of its message
+pub(crate) fn classify(error: anyhow::Error, wrap: fn(String) -> Error) -> anyhow::Error {
+    if error.is::<Error>() || error.is::<ValidationErrors>() || error.is::<serde_json::Error>() {
+        return error;
+    }
+
+    wrap(error.to_string()).into()
+}
+
+// Anything wrong with a configuration that parsed is reported as a validation error
+pub fn create_game(game_type: &GameType, payload: &str, shuffler: Shuffler) -> Result<Box<dyn Game>> {
+    build_game(game_type, payload, shuffler).map_err(|e| classify(e, Error::Validation))
+}
+
+fn build_game(game_type: &GameType, payload: &str, shuffler: Shuffler) -> Result<Box<dyn Game>> {
     match game_type {
         GameType::Fts => {
+            let (wager_map, house_id, currency_rules, settings) = parse_config::<FtsWagerType, FtsSettingsDto>(payload)?;
 
-            match serde_json::from_str::<ConfigDto<FtsWagerType>>(payload) {
-                Ok(config_dto) => {
+            match settings.odds {
+                Some(OddsDto::Auto(_)) => {
+                    let fts_config = config::Fts::auto_priced(wager_map, house_id, currency_rules, settings.house_edge.unwrap())?;
+                    Ok(Box::new(Fts::new(fts_config, shuffler)?))
+                },
+                Some(OddsDto::Explicit(odds)) => Ok(Box::new(Fts::new(config::Fts::new(wager_map, house_id, currency_rules, Some(odds))?, shuffler)?)),
+                None => Ok(Box::new(Fts::new(config::Fts::new(wager_map, house_id, currency_rules, None)?, shuffler)?))
+            }
+        },
+        GameType::Cta => {
+            let (wager_map, house_id, currency_rules, settings) = parse_config::<CtaWagerType, CtaSettingsDto>(payload)?;
+            let cta_config = config::Cta::new(wager_map, house_id, currency_rules, settings.cuts)?;
+            Ok(Box::new(Cta::new(cta_config, shuffler)?))
+        }
+    }
+}
 
-                    if config_dto.wager_map.is_empty() {
-                        return Err(Error::ParseConfig("Empty wager map".to_string()).into());
-                    }
+// Reads the wagers one by one so that every malformed wager is reported, not only the first one
+fn parse_config<T, S>(payload: &str) -> Result<(config::WagerMap<T>, String, CurrencyRules, S)>
+    where T: DeserializeOwned, S: DeserializeOwned + SettingsDto {
 
-                    let fts_config = config::Fts::new(
-                        config_dto.wager_map,
-                        config_dto.house_id.
-                            ok_or(anyhow!(Error::ParseConfig("This is an edged game, house id must exist".to_string())))?,
-                        None
-                    )?;
-                    Ok(Box::new(Fts::new(fts_config)?))
-                },
-                Err(e) => Err(Error::ParseConfig(e.to_string()).into())
+    let value: serde_json::Value = serde_json::from_str(payload)?;
+    let mut violations = Violations::new();
+    let mut wager_map = config::WagerMap::new();
+
+    match value.get("wagers") {
+        Some(serde_json::Value::Object(players)) => {
+            for (player_id, wagers) in players {
+                let Some(wagers) = wagers.as_array() else {
+                    violations.add(pointer(&["wagers", player_id]), "Wagers must be an array");
+                    continue;
+                };
+
+                let wagers = wagers.iter()
+                    .enumerate()
+                    .filter_map(|(i, wager)| violations.deserialize(&pointer(&["wagers", player_id, &i.to_string()]), wager))
+                    .collect();
+
+                wager_map.insert(Player::new(player_id.clone()), wagers);
             }
         },
-        _ => Err(Error::UnknownGame.into())
+        Some(_) => violations.add(pointer(&["wagers"]), "Wagers must be an object keyed by player id"),
+        None => violations.add(pointer(&["wagers"]), "Wagers are missing")
+    }
+
+    let house_id = match value.get("house_id") {
+        Some(house_id) => violations.deserialize::<String>(&pointer(&["house_id"]), house_id),
+        None => {
+            violations.add(pointer(&["house_id"]), "This is an edged game, house id must exist");
+            None
+        }
+    };
+
+    if value.get("client_seeds").is_some() {
+        violations.add(pointer(&["client_seeds"]), "Client seeds are submitted once the game is created and its commitment is published");
+    }
+
+    let currency_rules = violations.deserialize::<CurrencyRules>("", &value);
+    let settings = violations.deserialize::<S>("", &value);
+
+    if let Some(settings) = &settings {
+        settings.validate(&mut violations);
     }
-}
\ No newline at end of file
+
+    violations.into_result()?;
+
+    Ok((wager_map, house_id.unwrap(), currency_rules.unwrap(), settings.unwrap()))
+}
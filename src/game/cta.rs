@@ -1,27 +1,57 @@
+mod cut;
+
+use std::collections::BTreeMap;
+use crate::card::Card;
+use crate::game::cta::cut::Composition;
 use crate::deck::Deck;
+use crate::dto::{GameDto, WagerOddsDto};
 use crate::config::CtaWagerType;
 use crate::config;
-use crate::game::{Game, GameType};
-use anyhow::Result;
+use crate::game;
+use crate::game::{classify, Game, GameType, Snapshot, WagerChange};
+use crate::game::Error::{BettingClosed, InvalidTransition, RejectedTransition, SeedingClosed, Validation};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use crate::money::{Currency, Money};
 use crate::payout::Payout;
-use crate::state::State;
+use crate::shuffler::{Seed, Shuffler};
+use crate::state::{CtaState, State};
 use crate::state::GameState::*;
-use crate::transition::Transition;
+use crate::transition::{CtaTransition, Transition};
+use crate::transition::GameTransition::{End, Start};
+
+// Which side of the cut reached an ace first
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Outcome {
+    Forward,
+    Reverse
+}
 
-struct Cta {
+#[derive(Serialize, Deserialize)]
+pub struct Cta {
     deck_pool: Vec<Deck>,
+    shuffler: Shuffler,
+    composition: Composition,
     config: config::Cta,
     enforce_optimal_cut: bool,
-    state: State
+    state: State,
+    cuts_made: u8,
+    outcome: Option<Outcome>
 }
 
 impl Cta {
-    pub fn new(config: config::Cta) -> Result<Self> {
+    pub fn new(config: config::Cta, shuffler: Shuffler) -> Result<Self> {
+        let deck_pool = vec![Deck::default()];
+
         let mut game = Cta {
-            deck_pool: vec![Deck::default()],
+            shuffler,
+            composition: Composition::of(deck_pool.iter()),
+            deck_pool,
             config,
             state: State::Game(Setup),
-            enforce_optimal_cut: false
+            enforce_optimal_cut: false,
+            cuts_made: 0,
+            outcome: None
         };
 
         game.apply_config()?;
@@ -33,15 +63,177 @@ impl Cta {
 
         // optimal cut is enforced when there are reverse wagers
         if self.config.get_base_config().get_wagers()
-            .iter()
-            .flat_map( |(_, wagers)| wagers.iter())
+            .values()
+            .flat_map(|wagers| wagers.iter())
             .any( |wager| *wager.get_wager_type() == CtaWagerType::Reverse) {
 
                 self.enforce_optimal_cut = true;
         }
 
+        // every cut needs a sub-deck of at least 2 cards to split
+        if usize::from(self.config.get_cut_count()) >= self.composition.get_cards() {
+            return Err(anyhow!(
+                "{} cuts cannot be made in a pool of {} cards", self.config.get_cut_count(), self.composition.get_cards()
+            ));
+        }
+
         Ok(())
     }
+
+    fn transition_state(&mut self, transition: Transition) -> Result<State> {
+        if let Transition::Game(End { reason }) = &transition {
+            return game::void(&self.state, reason);
+        }
+
+        let new_state = match &self.state {
+            State::Game(Setup) => {
+                match &transition {
+                    Transition::Game(Start) => {
+                        self.start_game();
+                        State::Cta(CtaState::AwaitCut)
+                    },
+                    _ => return Err(InvalidTransition.into())
+                }
+            },
+            State::Cta(CtaState::AwaitCut) => {
+                match &transition {
+                    Transition::Cta(CtaTransition::Cut { deck_index, position }) => {
+                        self.cut(usize::from(*deck_index), usize::from(*position))?;
+
+                        if self.outcome.is_some() {
+                            State::Game(Ended)
+                        } else {
+                            State::Cta(CtaState::AwaitCut)
+                        }
+                    },
+                    _ => return Err(InvalidTransition.into())
+                }
+            },
+            _ => return Err(InvalidTransition.into())
+        };
+
+        Ok(new_state)
+    }
+
+    fn change_config(&mut self, change: WagerChange) -> Result<()> {
+        let base_config = self.config.get_base_config();
+
+        let config = config::Cta::new(
+            game::change_wagers(base_config.get_wagers(), change)?,
+            base_config.get_house_id().to_string(),
+            base_config.get_currency_rules().clone(),
+            Some(self.config.get_cut_count())
+        )?;
+
+        self.config = config;
+        Ok(())
+    }
+
+    fn start_game(&mut self) {
+        for deck in self.deck_pool.iter_mut() {
+            deck.shuffle(&mut self.shuffler);
+        }
+    }
+
+    // The cut splits the deck right after the card at `position`, the bottom part goes into the pool right
+    // after the deck it was cut from. On the deciding cut the top part is read in reverse starting from
+    // `position`, the bottom part is read forward from its first card.
+    fn cut(&mut self, deck_index: usize, position: usize) -> Result<()> {
+        let deciding_cut = self.cuts_made + 1 == self.config.get_cut_count();
+
+        if deck_index >= self.deck_pool.len() {
+            return Err(anyhow!("Deck index {} is out of range", deck_index));
+        }
+
+        if deciding_cut && self.enforce_optimal_cut {
+            let optimal_cuts = self.get_optimal_cuts();
+
+            if !optimal_cuts.contains(&(deck_index, position)) {
+                return Err(anyhow!(
+                    "Cut at deck {} position {} is not optimal, expected one of {:?}", deck_index, position, optimal_cuts
+                ));
+            }
+        }
+
+        let mut top = self.deck_pool[deck_index].clone();
+        let bottom = top.split(position)?;
+
+        if deciding_cut {
+            let forward = Self::distance_to_ace(bottom.get_cards().iter());
+            let reverse = Self::distance_to_ace(top.get_cards().iter().rev());
+
+            // the Cta rules do not settle ties, such a cut is refused and the game can be cut again or voided
+            self.outcome = Some(match (forward, reverse) {
+                (Some(f), Some(r)) if f < r => Outcome::Forward,
+                (Some(f), Some(r)) if f > r => Outcome::Reverse,
+                (Some(_), None) => Outcome::Forward,
+                (None, Some(_)) => Outcome::Reverse,
+                _ => return Err(anyhow!("Cut at deck {} position {} is a tie, which the Cta rules do not cover", deck_index, position))
+            });
+        }
+
+        self.deck_pool[deck_index] = top;
+        self.deck_pool.insert(deck_index + 1, bottom);
+        self.cuts_made += 1;
+
+        Ok(())
+    }
+
+    fn get_optimal_cuts(&self) -> Vec<(usize, usize)> {
+        cut::optimal_cuts(
+            &self.composition,
+            &self.deck_pool,
+            self.config.get_base_config().get_wagers()
+                .values()
+                .flat_map(|wagers| wagers.iter())
+                .map(|wager| wager.get_wager_type())
+        )
+    }
+
+    // Number of cards read until the first ace, the ace included
+    fn distance_to_ace<'a>(mut cards: impl Iterator<Item = &'a Card>) -> Option<usize> {
+        cards
+            .position(|card| card.get_rank() == 1)
+            .map(|idx| idx + 1)
+    }
+
+    // Payouts of the game if it ended with `outcome`
+    fn get_payout_of(&self, outcome: Outcome) -> Result<Vec<Payout<'_>>> {
+        let mut payouts: Vec<Payout> = Vec::new();
+        let mut house_payouts: BTreeMap<&Currency, Money> = BTreeMap::new();
+
+        for (player, wager_vec) in self.config.get_base_config().get_wagers().iter() {
+
+            for wager in wager_vec {
+
+                // even money
+                let amount = match (outcome, wager.get_wager_type()) {
+                    (Outcome::Forward, CtaWagerType::Forward) |
+                    (Outcome::Reverse, CtaWagerType::Reverse) => wager.amount,
+                    _ => Money::ZERO.checked_sub(wager.amount)?
+                };
+
+                if !amount.is_zero() {
+                    payouts.push(Payout::new(player.get_id(), Some(wager), wager.get_currency(), amount)?);
+                }
+
+                let house_payout = house_payouts.entry(wager.get_currency()).or_insert(Money::ZERO);
+                *house_payout = house_payout.checked_sub(amount)?;
+            }
+        }
+
+        for (currency, house_payout) in house_payouts {
+            if !house_payout.is_zero() {
+                payouts.push(
+                    Payout::new::<CtaWagerType>(
+                        self.config.get_base_config().get_house_id(), None, currency, house_payout
+                    )?
+                );
+            }
+        }
+
+        Ok(payouts)
+    }
 }
 
 impl Game for Cta {
@@ -49,15 +241,425 @@ impl Game for Cta {
         GameType::Cta
     }
 
+    fn get_state(&self) -> &State {
+        &self.state
+    }
+
+    fn get_seed(&self) -> &Seed {
+        self.shuffler.get_seed()
+    }
+
+    fn get_deck_pool(&self) -> &[Deck] {
+        &self.deck_pool
+    }
+
     fn transition(&mut self, transition: Transition) -> Result<()> {
-        todo!()
+
+        let new_state = self.transition_state(transition).map_err(|e| classify(e, RejectedTransition))?;
+
+        self.state = new_state;
+        Ok(())
     }
 
     fn get_valid_transitions(&self) -> Vec<Transition> {
-        todo!()
+
+        let mut transitions: Vec<Transition> = Vec::new();
+
+        match self.state {
+            State::Game(Setup) => transitions.push(Transition::Game(Start)),
+            State::Cta(CtaState::AwaitCut) => {
+                // only the recommended cuts are listed, other positions are still accepted unless enforced
+                for (deck_index, position) in self.get_optimal_cuts() {
+                    transitions.push(Transition::Cta(CtaTransition::Cut {
+                        deck_index: deck_index as u8,
+                        position: position as u8
+                    }));
+                }
+            },
+            _ => {}
+        }
+
+        transitions
+    }
+
+    fn reseed(&mut self, shuffler: Shuffler) -> Result<()> {
+        if self.state != State::Game(Setup) {
+            return Err(SeedingClosed.into());
+        }
+
+        self.shuffler = shuffler;
+        Ok(())
+    }
+
+    fn change_wagers(&mut self, change: WagerChange) -> Result<()> {
+        if self.state != State::Game(Setup) {
+            return Err(BettingClosed.into());
+        }
+
+        self.change_config(change).map_err(|e| classify(e, Validation))?;
+
+        // reverse wagers may have been cancelled
+        self.enforce_optimal_cut = false;
+        self.apply_config()
+    }
+
+    fn get_payout(&self) -> Result<Vec<Payout<'_>>> {
+        match (&self.state, &self.outcome) {
+            (State::Game(Ended), Some(outcome)) => self.get_payout_of(*outcome),
+            (State::Game(Voided { .. }), _) => Ok(game::refunds(self.config.get_base_config().get_wagers())),
+            _ => Ok(Vec::new())
+        }
+    }
+
+    fn get_house_max_losses(&self) -> Result<Vec<Payout<'_>>> {
+        let outcomes = [Outcome::Forward, Outcome::Reverse].into_iter()
+            .map(|outcome| self.get_payout_of(outcome))
+            .collect::<Result<Vec<Vec<Payout>>>>()?;
+
+        Ok(game::house_max_losses(outcomes))
+    }
+
+    // Even money, a wager never loses more than its amount
+    fn get_max_losses(&self) -> Result<Vec<Payout<'_>>> {
+        let mut max_losses = Vec::new();
+
+        for (player, wagers) in self.config.get_base_config().get_wagers() {
+            for wager in wagers {
+                max_losses.push(Payout::new(player.get_id(), Some(wager), wager.get_currency(), Money::ZERO.checked_sub(wager.amount)?)?);
+            }
+        }
+
+        Ok(max_losses)
+    }
+
+    fn get_details(&self) -> GameDto<'_> {
+        GameDto::Cta { state: &self.state, config: &self.config, outcome: self.outcome }
+    }
+
+    // Odds of the first recommended cut, Cta wagers risk their amount only
+    fn get_wager_odds(&self) -> Vec<WagerOddsDto<'_>> {
+        let analysis = match self.get_optimal_cuts().first() {
+            Some((deck_index, position)) => cut::analyze(&self.composition, self.deck_pool[*deck_index].len(), *position),
+            None => return Vec::new()
+        };
+
+        self.config.get_base_config().get_wagers().iter()
+            .flat_map(|(player, wagers)| wagers.iter().map(move |wager| (player, wager)))
+            .map(|(player, wager)| {
+                let expected_value = analysis.get_expected_value(wager.get_wager_type());
+
+                WagerOddsDto {
+                    player_id: player.get_id(),
+                    wager_id: *wager.get_id(),
+                    currency: wager.get_currency(),
+                    win_probability: match wager.get_wager_type() {
+                        CtaWagerType::Forward => analysis.forward,
+                        CtaWagerType::Reverse => analysis.reverse
+                    },
+                    expected_payout: expected_value * wager.amount.to_f64(),
+                    max_loss: wager.amount.to_f64(),
+                    house_edge: -expected_value
+                }
+            })
+            .collect()
+    }
+
+    fn snapshot(&self) -> Result<Snapshot> {
+        Snapshot::of(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::config::CurrencyRules;
+    use crate::card::Suit::{Clubs, Hearts};
+    use crate::wager::Wager;
+    use crate::config::CtaWagerType::{Forward, Reverse};
+    use crate::player::Player;
+    use crate::game::test_util::{amount_of, sorted_payouts};
+    use super::*;
+
+    // ace of hearts at index 1 and 6, everything else is a two of clubs
+    fn fixed_deck() -> Deck {
+        Deck::from_cards(
+            (0..8)
+                .map(|i| if i == 1 || i == 6 { Card::new(1, Hearts) } else { Card::new(2, Clubs) })
+                .collect::<Result<Vec<Card>, _>>()
+                .unwrap()
+        )
+    }
+
+    fn started_game(wagers: Vec<(&str, Vec<Wager<CtaWagerType>>)>) -> Result<Cta> {
+        started_multi_cut_game(wagers, 1)
     }
 
-    fn get_payout(&self) -> Result<Vec<Payout>> {
-        todo!()
+    fn started_multi_cut_game(wagers: Vec<(&str, Vec<Wager<CtaWagerType>>)>, cut_count: u8) -> Result<Cta> {
+        let wager_map: HashMap<Player, Vec<Wager<CtaWagerType>>> = wagers
+            .into_iter()
+            .map(|(id, wagers)| (Player::new(id.to_string()), wagers))
+            .collect();
+
+        started_game_of(config::Cta::new(wager_map, "house".to_string(), CurrencyRules::default(), Some(cut_count))?)
     }
-}
\ No newline at end of file
+
+    fn started_game_of(config: config::Cta) -> Result<Cta> {
+        let mut game = Cta::new(config, Shuffler::from_entropy())?;
+        game.transition(Transition::Game(Start))?;
+        game.deck_pool = vec![fixed_deck()];
+        game.composition = Composition::of(game.deck_pool.iter());
+
+        // outcome tests cut anywhere in the deck
+        game.enforce_optimal_cut = false;
+
+        Ok(game)
+    }
+
+    #[test]
+    fn flow() -> Result<()>{
+
+        let player = Player::new("player1".to_string());
+        let wager_map: HashMap<Player, Vec<Wager<CtaWagerType>>> = HashMap::from(
+            [
+                (player, vec![Wager::new( 0, Forward, Money::from_minor(100))?])
+            ]
+        );
+
+        let config = config::Cta::new(wager_map, "house".to_string(), CurrencyRules::default(), None)?;
+
+        let mut game = Cta::new(config, Shuffler::from_entropy())?;
+
+        assert_eq!(game.get_valid_transitions(), vec![Transition::Game(Start)]);
+        game.transition(Transition::Game(Start))?;
+        assert_eq!(game.state, State::Cta(CtaState::AwaitCut));
+
+        // a shuffled deck may tie at any one position, which is refused
+        let cut = (0..51).find(|position| game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: *position })).is_ok());
+        assert!(cut.is_some());
+        assert_eq!(game.state, State::Game(Ended));
+        assert!(game.get_valid_transitions().is_empty());
+
+        let payout = game.get_payout()?;
+
+        // the house always balances the players
+        assert_eq!(payout.iter().map(|payout| payout.get_amount().get_minor()).sum::<i64>(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn forward_wins() -> Result<()> {
+        let mut game = started_game(vec![
+            ("player1", vec![Wager::new(0, Forward, Money::from_minor(100))?]),
+            ("player2", vec![Wager::new(1, Reverse, Money::from_minor(40))?])
+        ])?;
+
+        // forward reaches the ace at index 6 after 2 cards, reverse reaches index 1 after 4
+        game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 4 }))?;
+        assert_eq!(game.outcome, Some(Outcome::Forward));
+
+        let payouts = game.get_payout()?;
+        assert_eq!(amount_of(&payouts, "player1"), 100);
+        assert_eq!(amount_of(&payouts, "player2"), -40);
+        assert_eq!(amount_of(&payouts, "house"), -60);
+        Ok(())
+    }
+
+    #[test]
+    fn house_line_per_currency() -> Result<()> {
+        let wagers: Vec<Wager<CtaWagerType>> = serde_json::from_str(r#"[
+            { "id": 0, "wager_type": "Forward", "amount": 100, "currency": "EUR" },
+            { "id": 1, "wager_type": "Forward", "amount": 30, "currency": "USD" }
+        ]"#)?;
+        let rules: CurrencyRules = serde_json::from_str(r#"{ "mixed_currencies": true }"#)?;
+
+        let mut game = started_game_of(
+            config::Cta::new(HashMap::from([(Player::new("player1".to_string()), wagers)]), "house".to_string(), rules, None)?
+        )?;
+        game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 4 }))?;
+
+        let mut house: Vec<(String, i64)> = game.get_payout()?.iter()
+            .filter(|payout| payout.get_player_id() == "house")
+            .map(|payout| (payout.get_currency().to_string(), payout.get_amount().get_minor()))
+            .collect();
+        house.sort();
+
+        assert_eq!(house, vec![("EUR".to_string(), -100), ("USD".to_string(), -30)]);
+        Ok(())
+    }
+
+    #[test]
+    fn reverse_wins() -> Result<()> {
+        let mut game = started_game(vec![
+            ("player1", vec![Wager::new(0, Forward, Money::from_minor(100))?, Wager::new(1, Reverse, Money::from_minor(30))?])
+        ])?;
+
+        // reverse reaches the ace at index 1 after 2 cards, forward reaches index 6 after 4
+        game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 2 }))?;
+        assert_eq!(game.outcome, Some(Outcome::Reverse));
+
+        let payouts = game.get_payout()?;
+        assert_eq!(amount_of(&payouts, "player1"), -70);
+        assert_eq!(amount_of(&payouts, "house"), 70);
+        Ok(())
+    }
+
+    #[test]
+    fn tie_is_refused() -> Result<()> {
+        let mut game = started_game(vec![
+            ("player1", vec![Wager::new(0, Forward, Money::from_minor(100))?])
+        ])?;
+
+        // both sides reach an ace after 3 cards
+        assert!(game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 3 })).is_err());
+        assert_eq!(game.state, State::Cta(CtaState::AwaitCut));
+        assert_eq!(game.outcome, None);
+        assert_eq!(game.deck_pool.iter().map(|deck| deck.len()).collect::<Vec<usize>>(), vec![8]);
+
+        game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 4 }))?;
+        assert_eq!(game.outcome, Some(Outcome::Forward));
+        Ok(())
+    }
+
+    #[test]
+    fn no_payout_before_end() -> Result<()> {
+        let game = started_game(vec![
+            ("player1", vec![Wager::new(0, Forward, Money::from_minor(100))?])
+        ])?;
+
+        assert!(game.get_payout()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn voided_mid_play() -> Result<()> {
+        let mut game = started_multi_cut_game(vec![
+            ("player1", vec![Wager::new(0, Forward, Money::from_minor(100))?, Wager::new(1, Reverse, Money::from_minor(50))?]),
+            ("player2", vec![Wager::new(2, Reverse, Money::from_minor(100))?])
+        ], 2)?;
+        game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 3 }))?;
+
+        let void = |reason: &str| Transition::Game(End { reason: reason.to_string() });
+        assert!(game.transition(void(" ")).is_err());
+        game.transition(void("misdeal"))?;
+
+        assert_eq!(game.state, State::Game(Voided { reason: "misdeal".to_string() }));
+        assert!(game.get_valid_transitions().is_empty());
+
+        // every wager gets a record and nothing changes hands
+        let payouts = game.get_payout()?;
+        assert_eq!(payouts.len(), 3);
+        assert!(payouts.iter().all(|payout| payout.get_amount().is_zero() && payout.get_wager_id().is_some()));
+
+        assert!(game.transition(void("again")).is_err());
+        assert!(game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 1 })).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_cut() -> Result<()> {
+        let mut game = started_game(vec![
+            ("player1", vec![Wager::new(0, Forward, Money::from_minor(100))?])
+        ])?;
+
+        assert!(game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 1, position: 3 })).is_err());
+        assert!(game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 7 })).is_err());
+        assert!(game.transition(Transition::Game(Start)).is_err());
+        assert_eq!(game.state, State::Cta(CtaState::AwaitCut));
+        Ok(())
+    }
+
+    #[test]
+    fn enforced_optimal_cut() -> Result<()> {
+        let mut game = started_game(vec![
+            ("player1", vec![Wager::new(0, Reverse, Money::from_minor(100))?])
+        ])?;
+        game.enforce_optimal_cut = true;
+
+        // 8 cards with 2 aces are only even when cut in half
+        assert_eq!(
+            game.get_valid_transitions(),
+            vec![Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 3 })]
+        );
+
+        assert!(game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 2 })).is_err());
+        assert_eq!(game.state, State::Cta(CtaState::AwaitCut));
+
+        // the fixed deck ties when cut in half, moving its second ace to index 5 does not change the composition
+        game.deck_pool = vec![Deck::from_cards(
+            (0..8).map(|i| if i == 1 || i == 5 { Card::new(1, Hearts) } else { Card::new(2, Clubs) }).collect::<Result<Vec<Card>, _>>()?
+        )];
+
+        game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 3 }))?;
+        assert_eq!(game.state, State::Game(Ended));
+        Ok(())
+    }
+
+    #[test]
+    fn reverse_wager_enforces_optimal_cut() -> Result<()> {
+        let forward_only = started_game(vec![("player1", vec![Wager::new(0, Forward, Money::from_minor(100))?])])?;
+        let player_map = HashMap::from([
+            (Player::new("player1".to_string()), vec![Wager::new(0, Reverse, Money::from_minor(100))?])
+        ]);
+        let with_reverse = Cta::new(config::Cta::new(player_map, "house".to_string(), CurrencyRules::default(), None)?, Shuffler::from_entropy())?;
+
+        assert!(!forward_only.enforce_optimal_cut);
+        assert!(with_reverse.enforce_optimal_cut);
+        Ok(())
+    }
+
+    #[test]
+    fn multi_stage_cut() -> Result<()> {
+        let mut game = started_multi_cut_game(vec![
+            ("player1", vec![Wager::new(0, Forward, Money::from_minor(100))?])
+        ], 2)?;
+
+        // the first cut only moves the bottom 3 cards into their own deck
+        game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 4 }))?;
+        assert_eq!(game.state, State::Cta(CtaState::AwaitCut));
+        assert_eq!(game.outcome, None);
+        assert_eq!(game.deck_pool.iter().map(|deck| deck.len()).collect::<Vec<usize>>(), vec![5, 3]);
+        assert!(game.get_payout()?.is_empty());
+
+        // cutting the top deck after its first card: forward reaches the ace at index 1 straight away
+        game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 0 }))?;
+        assert_eq!(game.state, State::Game(Ended));
+        assert_eq!(game.outcome, Some(Outcome::Forward));
+        assert_eq!(game.deck_pool.iter().map(|deck| deck.len()).collect::<Vec<usize>>(), vec![1, 4, 3]);
+        assert_eq!(amount_of(&game.get_payout()?, "player1"), 100);
+        Ok(())
+    }
+
+    #[test]
+    fn too_many_cuts() -> Result<()> {
+        let player_map = HashMap::from([
+            (Player::new("player1".to_string()), vec![Wager::new(0, Forward, Money::from_minor(100))?])
+        ]);
+
+        // a 52 card deck splits at most 51 times
+        assert!(config::Cta::new(player_map, "house".to_string(), CurrencyRules::default(), Some(52)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_round_trip() -> Result<()> {
+        let mut game = started_multi_cut_game(vec![
+            ("player1", vec![Wager::new(0, Forward, Money::from_minor(100))?]),
+            ("player2", vec![Wager::new(1, Reverse, Money::from_minor(40))?])
+        ], 2)?;
+        game.transition(Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 4 }))?;
+
+        let mut restored = crate::game::restore(serde_json::from_str(&serde_json::to_string(&game.snapshot()?)?)?)?;
+        assert_eq!(restored.get_state(), &State::Cta(CtaState::AwaitCut));
+        assert_eq!(restored.get_valid_transitions(), game.get_valid_transitions());
+
+        // the restored game keeps enforcement off and cuts the same fixed pool
+        let cut = Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 0 });
+        game.transition(cut.clone())?;
+        restored.transition(cut)?;
+
+        assert_eq!(restored.get_state(), &State::Game(Ended));
+        assert_eq!(sorted_payouts(restored.as_ref())?, sorted_payouts(&game)?);
+        assert_eq!(amount_of(&restored.get_payout()?, "player1"), 100);
+        Ok(())
+    }
+}
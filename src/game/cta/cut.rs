@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use crate::config::CtaWagerType;
+use crate::deck::Deck;
+
+// Two cuts whose scores are within this distance are considered equally good
+const SCORE_EPSILON: f64 = 1e-9;
+
+// Card and ace counts of the deck the pool was built from. Sub-decks of a shuffled deck are random samples
+// of it, so their cuts are analysed against the source composition rather than their hidden content.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct Composition {
+    cards: usize,
+    aces: usize
+}
+
+impl Composition {
+    pub fn of<'a>(decks: impl Iterator<Item = &'a Deck>) -> Self {
+        decks.fold(Composition { cards: 0, aces: 0 }, |composition, deck| Composition {
+            cards: composition.cards + deck.len(),
+            aces: composition.aces + deck.get_cards().iter().filter(|card| card.get_rank() == 1).count()
+        })
+    }
+
+    pub fn get_cards(&self) -> usize {
+        self.cards
+    }
+}
+
+// Outcome probabilities of a single cut, taken over every possible order of the deck
+#[derive(Debug, PartialEq)]
+pub struct CutAnalysis {
+    pub position: usize,
+    pub forward: f64,
+    pub reverse: f64,
+    pub tie: f64
+}
+
+impl CutAnalysis {
+    // Expected result of a unit even money wager on the given direction
+    pub fn get_expected_value(&self, wager_type: &CtaWagerType) -> f64 {
+        match wager_type {
+            CtaWagerType::Forward => self.forward - self.reverse,
+            CtaWagerType::Reverse => self.reverse - self.forward
+        }
+    }
+}
+
+// Analyses the cut made right after the card at `position` of a deck holding `deck_len` cards. Both sides are
+// read one card at a time, away from the cut, until one of them shows an ace. Only the composition matters
+// since the order is unknown to the dealer.
+pub fn analyze(composition: &Composition, deck_len: usize, position: usize) -> CutAnalysis {
+    let mut remaining = composition.cards as f64;
+    let aces = composition.aces as f64;
+
+    let reverse_len = position + 1;
+    let forward_len = deck_len.saturating_sub(position + 1);
+
+    let mut analysis = CutAnalysis { position, forward: 0.0, reverse: 0.0, tie: 0.0 };
+
+    // probability that every card read so far is not an ace
+    let mut no_ace = 1.0;
+
+    for distance in 1..=std::cmp::max(forward_len, reverse_len) {
+        let forward_read = distance <= forward_len;
+        let reverse_read = distance <= reverse_len;
+
+        if forward_read && reverse_read {
+            let both = aces / remaining * (aces - 1.0) / (remaining - 1.0);
+            let one_side = aces / remaining * (remaining - aces) / (remaining - 1.0);
+
+            analysis.tie += no_ace * both;
+            analysis.forward += no_ace * one_side;
+            analysis.reverse += no_ace * one_side;
+
+            no_ace *= (remaining - aces) / remaining * (remaining - aces - 1.0) / (remaining - 1.0);
+            remaining -= 2.0;
+        } else {
+            let ace = aces / remaining;
+
+            if forward_read {
+                analysis.forward += no_ace * ace;
+            } else {
+                analysis.reverse += no_ace * ace;
+            }
+
+            no_ace *= (remaining - aces) / remaining;
+            remaining -= 1.0;
+        }
+
+        if no_ace <= 0.0 {
+            break;
+        }
+    }
+
+    // neither side holds an ace
+    analysis.tie += no_ace;
+
+    analysis
+}
+
+// Returns every (deck index, position) cut that keeps the wagers closest to even. A cut is scored by the
+// largest edge it gives to any wagered direction, the lowest score of each deck wins.
+pub fn optimal_cuts<'a>(composition: &Composition,
+                        deck_pool: &[Deck],
+                        wager_types: impl Iterator<Item = &'a CtaWagerType> + Clone) -> Vec<(usize, usize)> {
+    let mut cuts = Vec::new();
+
+    for (deck_index, deck) in deck_pool.iter().enumerate() {
+        if deck.len() <= 1 {
+            continue;
+        }
+
+        let scores: Vec<(usize, f64)> = (0..deck.len() - 1)
+            .map(|position| {
+                let analysis = analyze(composition, deck.len(), position);
+                let score = wager_types.clone()
+                    .map(|wager_type| analysis.get_expected_value(wager_type).abs())
+                    .fold(0.0, f64::max);
+
+                (position, score)
+            })
+            .collect();
+
+        let best = scores.iter().map(|(_, score)| *score).fold(f64::INFINITY, f64::min);
+
+        cuts.extend(
+            scores.iter()
+                .filter(|(_, score)| *score - best <= SCORE_EPSILON)
+                .map(|(position, _)| (deck_index, *position))
+        );
+    }
+
+    cuts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::Card;
+    use crate::card::Suit::{Clubs, Hearts};
+    use super::*;
+
+    fn assert_distribution(analysis: &CutAnalysis) {
+        assert!((analysis.forward + analysis.reverse + analysis.tie - 1.0).abs() < SCORE_EPSILON);
+    }
+
+    #[test]
+    fn even_cut_is_symmetric() {
+        let deck = Deck::default();
+        let analysis = analyze(&Composition::of([&deck].into_iter()), deck.len(), 25);
+
+        assert_distribution(&analysis);
+        assert!((analysis.forward - analysis.reverse).abs() < SCORE_EPSILON);
+    }
+
+    #[test]
+    fn short_side_is_disadvantaged() {
+        let deck = Deck::default();
+        let analysis = analyze(&Composition::of([&deck].into_iter()), deck.len(), 2);
+
+        assert_distribution(&analysis);
+        assert!(analysis.forward > analysis.reverse);
+    }
+
+    #[test]
+    fn single_card_sides() {
+        let deck = Deck::from_cards(vec![Card::new(1, Hearts).unwrap(), Card::new(2, Clubs).unwrap()]);
+        let analysis = analyze(&Composition::of([&deck].into_iter()), deck.len(), 0);
+
+        assert_distribution(&analysis);
+        assert!((analysis.forward - 0.5).abs() < SCORE_EPSILON);
+        assert!((analysis.reverse - 0.5).abs() < SCORE_EPSILON);
+    }
+
+    #[test]
+    fn no_ace_always_ties() {
+        let deck = Deck::from_cards((0..6).map(|_| Card::new(2, Clubs).unwrap()).collect());
+        let analysis = analyze(&Composition::of([&deck].into_iter()), deck.len(), 3);
+
+        assert_eq!(analysis.forward, 0.0);
+        assert_eq!(analysis.reverse, 0.0);
+        assert_eq!(analysis.tie, 1.0);
+    }
+
+    #[test]
+    fn optimal_cut_halves_the_deck() {
+        let deck_pool = vec![Deck::default()];
+        let composition = Composition::of(deck_pool.iter());
+
+        // 48 non aces are always exhausted after 24 cards on each side, so a 25/27 split is still even
+        assert_eq!(
+            optimal_cuts(&composition, &deck_pool, [CtaWagerType::Reverse].iter()),
+            vec![(0, 24), (0, 25), (0, 26)]
+        );
+    }
+
+    #[test]
+    fn optimal_cut_of_sub_decks() {
+        let mut deck_pool = vec![Deck::default()];
+        let composition = Composition::of(deck_pool.iter());
+
+        let bottom = deck_pool[0].split(10).unwrap();
+        deck_pool.push(bottom);
+
+        // an odd sub-deck can be cut on either side of its middle card
+        assert_eq!(
+            optimal_cuts(&composition, &deck_pool, [CtaWagerType::Forward].iter()),
+            vec![(0, 4), (0, 5), (1, 19), (1, 20)]
+        );
+    }
+
+    #[test]
+    fn sub_deck_uses_source_composition() {
+        let mut deck = Deck::default();
+        let composition = Composition::of([&deck].into_iter());
+
+        // the 11 cards kept hold a single ace, but the dealer cannot know that
+        deck.split(10).unwrap();
+        assert_eq!(deck.len(), 11);
+        assert_ne!(analyze(&composition, deck.len(), 5), analyze(&Composition::of([&deck].into_iter()), deck.len(), 5));
+    }
+}
@@ -1,28 +1,39 @@
-use std::cmp;
-use std::collections::HashMap;
+pub(crate) mod probability;
+
+use std::{cmp, iter};
+use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
+use crate::card::Card;
 use crate::deck::Deck;
+use crate::dto::{FtsResultDto, GameDto, WagerOddsDto};
+use crate::shuffler::{Seed, Shuffler};
 use crate::state::State;
-use crate::config::fts::{Fts as FtsConfig, FtsWagerType};
+use crate::config::fts::{get_flop_range_length, Fts as FtsConfig, FtsWagerType, Odds};
 use anyhow::{anyhow, Result};
-use crate::game::{Game, GameType};
-use crate::game::Error::InvalidTransition;
+use serde::{Deserialize, Serialize};
+use crate::game;
+use crate::game::{classify, Game, GameType, Snapshot, WagerChange};
+use crate::game::Error::{BettingClosed, InvalidTransition, RejectedTransition, SeedingClosed, Validation};
+use crate::money::{Currency, Money};
 use crate::payout::Payout;
 use crate::state::GameState::*;
 use crate::transition::Transition;
-use crate::transition::GameTransition::Start;
+use crate::transition::GameTransition::{End, Start};
 
+#[derive(Serialize, Deserialize)]
 pub struct Fts {
     deck: Deck,
+    shuffler: Shuffler,
     config: FtsConfig,
     state: State,
     max_flop_count: u8,
     flopped_at: Option<u8>     // This is the ith flop where the first flop is 0
 }
 impl Fts {
-    pub fn new(config: FtsConfig) -> Result<Self> {
+    pub fn new(config: FtsConfig, shuffler: Shuffler) -> Result<Self> {
         let mut fts = Fts {
             deck: Deck::default(),
+            shuffler,
             config,
             state: State::Game(Setup),
             max_flop_count: 0,
@@ -53,6 +64,23 @@ impl Fts {
 
         Ok(())
     }
+
+    // Auto priced odds are priced again for the new wagers, any other paytable stays as the game was created with
+    fn change_config(&mut self, change: WagerChange) -> Result<()> {
+        let base_config = self.config.get_base_config();
+        let wagers = game::change_wagers(base_config.get_wagers(), change)?;
+        let house_id = base_config.get_house_id().to_string();
+        let currency_rules = base_config.get_currency_rules().clone();
+
+        let config = match self.config.get_house_edge() {
+            Some(house_edge) => FtsConfig::auto_priced(wagers, house_id, currency_rules, house_edge)?,
+            None => FtsConfig::new(wagers, house_id, currency_rules, Some(self.config.get_odds().clone()))?
+        };
+
+        self.config = config;
+        Ok(())
+    }
+
     fn can_start(&self) -> Result<()> {
         if self.max_flop_count == 0 {
             return Err(anyhow!("Game set to perform 0 flops"));
@@ -66,6 +94,10 @@ impl Fts {
     }
 
     fn transition_state(&mut self, transition: Transition) -> Result<State>{
+        if let Transition::Game(End { reason }) = &transition {
+            return game::void(&self.state, reason);
+        }
+
         let new_state = match &self.state {
             State::Game(Setup) => {
                 match &transition {
@@ -86,30 +118,101 @@ impl Fts {
     fn start_game(&mut self) -> Result<()>{
         self.can_start()?;
 
-        self.deck.shuffle();
+        self.deck.shuffle(&mut self.shuffler);
 
         match self.deck.deal_multi((self.max_flop_count * 3).into()) {
             None => return Err(anyhow!("Could not deal the required amount of cards")),
-            Some(cards) => {
-                let result = cards
-                    .chunks(3)
-                    .enumerate()
-                    .filter_map(|(i, chunk)| {
-                        if chunk[0].get_suit() == chunk[1].get_suit() && chunk[1].get_suit() == chunk[2].get_suit() {
-                            Some(i)
-                        } else {
-                            None
-                        }
-                    })
-                    .next();
-
-                if let Some(flopped_at) = result {
-                    self.flopped_at = Some(flopped_at as u8);
+            Some(cards) => self.flopped_at = find_first_flop(cards)
+        }
+
+        Ok(())
+    }
+
+    // Payouts of the game if the first flop happened at `flopped_at`
+    fn get_payout_at(&self, flopped_at: Option<u8>) -> Result<Vec<Payout<'_>>> {
+        let mut payouts: Vec<Payout> = Vec::new();
+        let mut house_payouts: BTreeMap<&Currency, Money> = BTreeMap::new();
+
+        for (player, wager_vec) in self.config.get_base_config().get_wagers().iter() {
+
+            for wager in wager_vec {
+
+                let amount = wager.amount.checked_mul(i64::from(get_multiplier(
+                    wager.get_wager_type(), self.config.get_odds(), flopped_at, self.get_max_possible_flop_count()
+                )))?;
+
+                if !amount.is_zero() {
+                    payouts.push(Payout::new(player.get_id(), Some(wager), wager.get_currency(), amount)?);
                 }
+
+                let house_payout = house_payouts.entry(wager.get_currency()).or_insert(Money::ZERO);
+                *house_payout = house_payout.checked_sub(amount)?;
             }
         }
 
-        Ok(())
+        for (currency, house_payout) in house_payouts {
+            if !house_payout.is_zero() {
+                payouts.push(
+                    Payout::new::<FtsWagerType>(
+                        self.config.get_base_config().get_house_id(), None, currency, house_payout
+                    )?
+                );
+            }
+        }
+
+        Ok(payouts)
+    }
+}
+
+// Index of the first three card chunk sharing a single suit, if any
+pub(crate) fn find_first_flop(cards: &[Card]) -> Option<u8> {
+    cards
+        .chunks_exact(3)
+        .position(|chunk| chunk[0].get_suit() == chunk[1].get_suit() && chunk[1].get_suit() == chunk[2].get_suit())
+        .map(|i| i as u8)
+}
+
+// Result of a unit wager given where the first flop happened, `flop_count` is the number of flops in a full deck
+pub(crate) fn get_multiplier(wager_type: &FtsWagerType, odds: &Odds, flopped_at: Option<u8>, flop_count: u8) -> i32 {
+    match wager_type {
+
+        FtsWagerType::FullDeck => match flopped_at {
+
+            // No flop, player loses their wager times number of flops for the deck
+            None => -i32::from(flop_count),
+
+            // Player wins full deck odds * wager - failed flops * wager or wager * (full deck odds - failed flops)
+            Some(flopped_at) => odds.get_full_deck() - i32::from(flopped_at)
+        },
+
+        FtsWagerType::AtFlop(flop) => match flopped_at {
+            // No flop, player loses the wager
+            None => -1,
+
+            // Player wins only if it's on the same flop, player only loses if it flopped after
+            Some(flopped_at) =>
+                if flopped_at == *flop {
+                    *odds.get_at_flop()
+                } else if flopped_at > *flop {
+                    -1
+                } else {
+                    0
+                }
+        },
+
+        FtsWagerType::FlopRange(flop_start, flop_end) => match flopped_at {
+            // No flop, player loses the wager * the length of the range
+            None => -i32::from(get_flop_range_length(*flop_start, *flop_end)),
+
+            // Player wins if it's within range, loses if flopped happened after the range
+            Some(flopped_at) => if flopped_at <= *flop_end && flopped_at >= *flop_start {
+                odds.get_flop_range_odds(*flop_start, *flop_end) - i32::from(flopped_at - flop_start)
+            } else if flopped_at > *flop_end {
+                -i32::from(get_flop_range_length(*flop_start, *flop_end))
+            } else {
+                0
+            }
+        }
     }
 }
 
@@ -118,9 +221,21 @@ impl Game for Fts {
         GameType::Fts
     }
 
+    fn get_state(&self) -> &State {
+        &self.state
+    }
+
+    fn get_seed(&self) -> &Seed {
+        self.shuffler.get_seed()
+    }
+
+    fn get_deck_pool(&self) -> &[Deck] {
+        std::slice::from_ref(&self.deck)
+    }
+
     fn transition(&mut self, transition: Transition) -> Result<()> {
 
-        let new_state = self.transition_state(transition)?;
+        let new_state = self.transition_state(transition).map_err(|e| classify(e, RejectedTransition))?;
 
         self.state = new_state;
         Ok(())
@@ -130,100 +245,120 @@ impl Game for Fts {
 
         let mut transitions: Vec<Transition> = Vec::new();
 
-        match self.state {
-            State::Game(Setup) => transitions.push(Transition::Game(Start)),
-            _ => {}
+        if self.state == State::Game(Setup) {
+            transitions.push(Transition::Game(Start));
         }
 
         transitions
     }
 
-    fn get_payout(&self) -> Result<Vec<Payout>> {
-
-        // match &self.flopped_at {
-        //     Some(at) => format!("Flopped at: {}\nCards: {:?}", at, self.deck.get_dealt_cards()),
-        //     None =>  format!("No flop\nCards: {:?}", self.deck.get_dealt_cards())
-        // }
-
-        let mut payouts: Vec<Payout> = Vec::new();
-
-        if self.state != State::Game(Ended) {
-            return Ok(payouts);
+    fn reseed(&mut self, shuffler: Shuffler) -> Result<()> {
+        if self.state != State::Game(Setup) {
+            return Err(SeedingClosed.into());
         }
 
-        let mut house_payout = 0;
-
-        for (player, wager_vec) in self.config.get_base_config().get_wagers().iter() {
+        self.shuffler = shuffler;
+        Ok(())
+    }
 
-            for wager in wager_vec {
+    fn change_wagers(&mut self, change: WagerChange) -> Result<()> {
+        if self.state != State::Game(Setup) {
+            return Err(BettingClosed.into());
+        }
 
-                let amount = match wager.get_wager_type() {
+        self.change_config(change).map_err(|e| classify(e, Validation))?;
 
-                    FtsWagerType::FullDeck => match &self.flopped_at {
+        // the deal is sized for the new wagers
+        self.max_flop_count = 0;
+        self.apply_config()
+    }
 
-                        // No flop, player loses their wager times number of flops for the deck
-                        None => -(wager.amount * i32::from(self.get_max_possible_flop_count())),
+    fn get_payout(&self) -> Result<Vec<Payout<'_>>> {
+        match &self.state {
+            State::Game(Ended) => self.get_payout_at(self.flopped_at),
+            State::Game(Voided { .. }) => Ok(game::refunds(self.config.get_base_config().get_wagers())),
+            _ => Ok(Vec::new())
+        }
+    }
 
-                        // Player wins full deck odds * wager - failed flops * wager or wager * (full deck odds - failed flops)
-                        Some(flopped_at) =>
-                            wager.amount * (self.config.get_odds().get_full_deck() - i32::from(*flopped_at))
-                    },
+    fn get_house_max_losses(&self) -> Result<Vec<Payout<'_>>> {
+        let outcomes = iter::once(None).chain((0..self.get_max_possible_flop_count()).map(Some))
+            .map(|flopped_at| self.get_payout_at(flopped_at))
+            .collect::<Result<Vec<Vec<Payout>>>>()?;
 
-                    FtsWagerType::AtFlop(flop) => match &self.flopped_at {
-                        // No flop, player loses the wager
-                        None => -wager.amount,
-
-                        // Player wins only if it's on the same flop, player only loses if it flopped after
-                        Some(flopped_at) =>
-                            if flopped_at == flop {
-                                wager.amount * self.config.get_odds().get_at_flop()
-                            } else if flopped_at > flop {
-                                -wager.amount
-                            } else {
-                                0
-                            }
-                    },
+        Ok(game::house_max_losses(outcomes))
+    }
 
-                    FtsWagerType::FlopRange(flop_start, flop_end) => match &self.flopped_at {
-                        // No flop, player loses the wager * the length of the range
-                        None => -( wager.amount * i32::from(flop_end - flop_start + 1) ),
-
-                        // Player wins if it's within range, loses if flopped happened after the range
-                        Some(flopped_at) => if flopped_at <= flop_end && flopped_at >= flop_start {
-                            wager.amount * (self.config.get_odds().get_flop_range() - i32::from(flopped_at - flop_start) )
-                        } else if flopped_at > flop_end {
-                            -( wager.amount * i32::from(flop_end - flop_start + 1) )
-                        } else {
-                            0
-                        }
-                    }
-                };
-
-                if amount != 0 {
-                    payouts.push(Payout::new(player.get_id(), Some(wager), amount)?);
+    // No flop is the worst outcome of every wager type, every outcome is checked all the same
+    fn get_max_losses(&self) -> Result<Vec<Payout<'_>>> {
+        let flop_count = self.get_max_possible_flop_count();
+        let mut max_losses = Vec::new();
+
+        for (player, wagers) in self.config.get_base_config().get_wagers() {
+            for wager in wagers {
+                let multiplier = iter::once(None).chain((0..flop_count).map(Some))
+                    .map(|flopped_at| get_multiplier(wager.get_wager_type(), self.config.get_odds(), flopped_at, flop_count))
+                    .min()
+                    .unwrap_or(0);
+
+                if multiplier < 0 {
+                    let amount = wager.amount.checked_mul(i64::from(multiplier))?;
+                    max_losses.push(Payout::new(player.get_id(), Some(wager), wager.get_currency(), amount)?);
                 }
-
-                house_payout -= amount;
             }
         }
 
-        if house_payout != 0 {
-            payouts.push(
-                Payout::new::<FtsWagerType>(
-                    self.config.get_base_config().get_house_id(), None, house_payout
-                )?
-            );
+        Ok(max_losses)
+    }
+
+    fn get_details(&self) -> GameDto<'_> {
+        GameDto::Fts {
+            state: &self.state,
+            config: &self.config,
+            result: (self.state == State::Game(Ended)).then(|| FtsResultDto {
+                flops: self.deck.get_dealt_cards().chunks(3).collect(),
+                flopped_at: self.flopped_at
+            })
         }
+    }
 
-        Ok(payouts)
+    fn get_wager_odds(&self) -> Vec<WagerOddsDto<'_>> {
+        let distribution = probability::flop_distribution(&self.deck, self.max_flop_count);
+
+        self.config.get_base_config().get_wagers().iter()
+            .flat_map(|(player, wagers)| wagers.iter().map(move |wager| (player, wager)))
+            .map(|(player, wager)| {
+                let wager_odds = probability::wager_odds(
+                    wager.get_wager_type(), self.config.get_odds(), &distribution, self.get_max_possible_flop_count()
+                );
+
+                WagerOddsDto {
+                    player_id: player.get_id(),
+                    wager_id: *wager.get_id(),
+                    currency: wager.get_currency(),
+                    win_probability: wager_odds.win_probability,
+                    expected_payout: wager_odds.expected_value * wager.amount.to_f64(),
+                    max_loss: f64::from(wager_odds.max_loss) * wager.amount.to_f64(),
+                    house_edge: wager_odds.house_edge
+                }
+            })
+            .collect()
+    }
+
+    fn snapshot(&self) -> Result<Snapshot> {
+        Snapshot::of(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use crate::config::CurrencyRules;
+    use crate::fairness::Fairness;
     use crate::wager::Wager;
-    use crate::config::fts::FtsWagerType::FullDeck;
+    use crate::config::fts::FtsWagerType::{AtFlop, FlopRange, FullDeck};
     use crate::player::Player;
+    use crate::game::test_util::{amount_of, sorted_payouts};
     use super::*;
 
     #[test]
@@ -232,14 +367,14 @@ mod tests {
         let player = Player::new("player1".to_string());
         let wager_map: HashMap<Player, Vec<Wager<FtsWagerType>>> = HashMap::from(
             [
-                (player, vec![Wager::new( 0, FullDeck, 100)?])
+                (player, vec![Wager::new( 0, FullDeck, Money::from_minor(100))?])
             ]
         );
 
-        let config = FtsConfig::new(wager_map, "house".to_string(), None)?;
+        let config = FtsConfig::new(wager_map, "house".to_string(), CurrencyRules::default(), None)?;
 
 
-        let mut game = Fts::new(config)?;
+        let mut game = Fts::new(config, Shuffler::from_entropy())?;
 
         game.transition(Transition::Game(Start))?;
 
@@ -250,4 +385,273 @@ mod tests {
         // println!("Result: {}", game.get_result());
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn ended_game(wagers: Vec<Wager<FtsWagerType>>, odds: Option<Odds>, flopped_at: Option<u8>) -> Result<Fts> {
+        let wager_map = HashMap::from([(Player::new("player1".to_string()), wagers)]);
+
+        let mut game = Fts::new(FtsConfig::new(wager_map, "house".to_string(), CurrencyRules::default(), odds)?, Shuffler::from_entropy())?;
+        game.state = State::Game(Ended);
+        game.flopped_at = flopped_at;
+
+        Ok(game)
+    }
+
+    #[test]
+    fn max_losses() -> Result<()> {
+        let game = ended_game(
+            vec![
+                Wager::new(0, FullDeck, Money::from_minor(10))?,
+                Wager::new(1, AtFlop(2), Money::from_minor(10))?,
+                Wager::new(2, FlopRange(1, 3), Money::from_minor(10))?
+            ],
+            None,
+            None
+        )?;
+
+        let mut max_losses: Vec<(u32, i64)> = game.get_max_losses()?.iter()
+            .map(|max_loss| (max_loss.get_wager_id().unwrap(), max_loss.get_amount().get_minor()))
+            .collect();
+        max_losses.sort();
+
+        // the same as the payouts when nothing flops
+        assert_eq!(max_losses, vec![(0, -170), (1, -10), (2, -30)]);
+        assert_eq!(amount_of(&game.get_payout()?, "player1"), -210);
+        Ok(())
+    }
+
+    #[test]
+    fn house_max_losses() -> Result<()> {
+        let game = ended_game(
+            vec![Wager::new(0, FullDeck, Money::from_minor(10))?, Wager::new(1, AtFlop(0), Money::from_minor(10))?],
+            None,
+            None
+        )?;
+
+        // both wagers win on the first flop: 17 * 10 each
+        let max_losses: Vec<i64> = game.get_house_max_losses()?.iter().map(|max_loss| max_loss.get_amount().get_minor()).collect();
+        assert_eq!(max_losses, vec![-340]);
+        Ok(())
+    }
+
+    #[test]
+    fn voided_before_start() -> Result<()> {
+        let mut game = ended_game(vec![Wager::new(0, FullDeck, Money::from_minor(10))?], None, None)?;
+        let void = Transition::Game(End { reason: "dealer error".to_string() });
+
+        // ended games keep their payouts
+        assert!(game.transition(void.clone()).is_err());
+
+        game.state = State::Game(Setup);
+        game.transition(void.clone())?;
+
+        assert_eq!(game.get_state(), &State::Game(Voided { reason: "dealer error".to_string() }));
+        assert!(game.get_valid_transitions().is_empty());
+        assert!(game.transition(Transition::Game(Start)).is_err());
+
+        let payouts = game.get_payout()?;
+        assert_eq!(payouts.len(), 1);
+        assert_eq!((payouts[0].get_wager_id(), payouts[0].get_amount()), (Some(0), Money::ZERO));
+        Ok(())
+    }
+
+    #[test]
+    fn betting_window() -> Result<()> {
+        let mut game = ended_game(vec![Wager::new(0, AtFlop(2), Money::from_minor(10))?], None, None)?;
+        game.state = State::Game(Setup);
+        assert_eq!(game.max_flop_count, 2);
+
+        let place = |player_id: &str, wager: &str| WagerChange::Place { player_id: player_id.to_string(), wager: serde_json::from_str(wager).unwrap() };
+
+        game.change_wagers(place("player2", r#"{ "id": 1, "wager_type": "FullDeck", "amount": 5 }"#))?;
+        assert_eq!(game.max_flop_count, 17);
+        game.change_wagers(WagerChange::Amend { wager_id: 1, amount: Money::from_minor(20) })?;
+        assert_eq!(game.config.get_base_config().get_wagers()[&Player::new("player2".to_string())][0].amount, Money::from_minor(20));
+
+        // wager ids stay unique across players
+        let error = game.change_wagers(place("player3", r#"{ "id": 0, "wager_type": "FullDeck", "amount": 5 }"#)).err().unwrap();
+        assert!(error.is::<crate::validation::ValidationErrors>());
+        let error = game.change_wagers(WagerChange::Amend { wager_id: 0, amount: Money::from_minor(-5) }).err().unwrap();
+        assert!(error.is::<crate::validation::ValidationErrors>());
+        let error = game.change_wagers(WagerChange::Cancel { wager_id: 7 }).err().unwrap();
+        assert!(matches!(error.downcast_ref::<game::Error>(), Some(game::Error::WagerNotFound(7))));
+
+        game.change_wagers(WagerChange::Cancel { wager_id: 1 })?;
+        assert_eq!(game.max_flop_count, 2);
+        assert_eq!(game.config.get_base_config().get_wagers().len(), 1);
+
+        game.transition(Transition::Game(Start))?;
+        let error = game.change_wagers(WagerChange::Cancel { wager_id: 0 }).err().unwrap();
+        assert!(matches!(error.downcast_ref::<game::Error>(), Some(game::Error::BettingClosed)));
+        Ok(())
+    }
+
+    #[test]
+    fn auto_odds_follow_wager_changes() -> Result<()> {
+        let wager_map = HashMap::from([(Player::new("player1".to_string()), vec![Wager::new(0, FullDeck, Money::from_minor(10))?])]);
+        let mut game = Fts::new(FtsConfig::auto_priced(wager_map, "house".to_string(), CurrencyRules::default(), 0.3)?, Shuffler::from_entropy())?;
+        assert_eq!(game.config.get_odds().get_flop_range_odds(0, 9), Odds::default().get_flop_range_odds(0, 9));
+
+        let wager = serde_json::from_str(r#"{ "id": 1, "wager_type": { "FlopRange": [0, 9] }, "amount": 10 }"#)?;
+        game.change_wagers(WagerChange::Place { player_id: "player2".to_string(), wager })?;
+
+        // the new wager is priced like the others rather than paid at the default odds
+        let priced = probability::auto_odds(game.config.get_base_config().get_wagers(), 0.3)?;
+        assert_eq!(game.config.get_odds().get_flop_range_odds(0, 9), priced.get_flop_range_odds(0, 9));
+        assert_ne!(game.config.get_odds().get_flop_range_odds(0, 9), Odds::default().get_flop_range_odds(0, 9));
+        assert_eq!(game.config.get_house_edge(), Some(0.3));
+        Ok(())
+    }
+
+    #[test]
+    fn custom_odds_payout() -> Result<()> {
+        let odds: Odds = serde_json::from_str(r#"{ "full_deck": 30, "at_flop": 12, "flop_range": 9 }"#)?;
+        let game = ended_game(
+            vec![Wager::new(0, FullDeck, Money::from_minor(10))?, Wager::new(1, AtFlop(2), Money::from_minor(10))?, Wager::new(2, FlopRange(1, 3), Money::from_minor(10))?],
+            Some(odds),
+            Some(2)
+        )?;
+
+        let payouts = game.get_payout()?;
+
+        // 10 * (30 - 2) + 10 * 12 + 10 * (9 - 1)
+        assert_eq!(amount_of(&payouts, "player1"), 480);
+        assert_eq!(amount_of(&payouts, "house"), -480);
+        Ok(())
+    }
+
+    #[test]
+    fn flop_range_paytable_payout() -> Result<()> {
+        let odds: Odds = serde_json::from_str(r#"{
+            "full_deck": 17, "at_flop": 17, "flop_range": 17,
+            "flop_range_table": [{ "length": 1, "odds": 14 }, { "length": 4, "odds": 5 }]
+        }"#)?;
+        let game = ended_game(
+            vec![Wager::new(0, FlopRange(3, 3), Money::from_minor(10))?, Wager::new(1, FlopRange(2, 5), Money::from_minor(10))?, Wager::new(2, FlopRange(0, 1), Money::from_minor(10))?],
+            Some(odds),
+            Some(3)
+        )?;
+
+        let payouts = game.get_payout()?;
+
+        // 10 * 14 + 10 * (5 - 1) - 10 * 2, the last range has no entry and loses
+        assert_eq!(amount_of(&payouts, "player1"), 160);
+        Ok(())
+    }
+
+    #[test]
+    fn wager_odds() -> Result<()> {
+        let game = ended_game(vec![Wager::new(0, FullDeck, Money::from_minor(10))?, Wager::new(1, AtFlop(0), Money::from_minor(10))?], None, None)?;
+        let wager_odds = game.get_wager_odds();
+
+        assert_eq!(wager_odds.len(), 2);
+        for odds in wager_odds {
+            assert!(odds.win_probability > 0.0 && odds.win_probability < 1.0);
+            assert!((odds.expected_payout + odds.house_edge * 10.0 * if odds.wager_id == 0 { 17.0 } else { 1.0 }).abs() < 1e-9);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn seeded_replay() -> Result<()> {
+        let play = |seed| -> Result<(Option<u8>, Vec<i64>)> {
+            let wager_map = HashMap::from([
+                (Player::new("player1".to_string()), vec![Wager::new(0, FullDeck, Money::from_minor(10))?, Wager::new(1, FlopRange(0, 4), Money::from_minor(10))?])
+            ]);
+
+            let mut game = Fts::new(FtsConfig::new(wager_map, "house".to_string(), CurrencyRules::default(), None)?, Shuffler::from_seed(seed))?;
+            game.transition(Transition::Game(Start))?;
+
+            let mut amounts: Vec<i64> = game.get_payout()?.iter().map(|payout| payout.get_amount().get_minor()).collect();
+            amounts.sort();
+
+            Ok((game.flopped_at, amounts))
+        };
+
+        for seed in [[0; 32], [1; 32], [42; 32]] {
+            assert_eq!(play(seed)?, play(seed)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn revealed_seeds_replay_the_game() -> Result<()> {
+        let client_seeds = BTreeMap::from([("player1".to_string(), "lucky".to_string())]);
+        let fairness = Fairness::new();
+
+        let wager_map = HashMap::from([(Player::new("player1".to_string()), vec![Wager::new(0, FullDeck, Money::from_minor(10))?])]);
+        let mut game = Fts::new(FtsConfig::new(wager_map, "house".to_string(), CurrencyRules::default(), None)?, fairness.get_shuffler())?;
+
+        // client seeds come in after the commitment was published
+        let fairness = fairness.with_client_seeds(client_seeds.clone());
+        game.reseed(fairness.get_shuffler())?;
+        game.transition(Transition::Game(Start))?;
+        assert!(game.reseed(fairness.get_shuffler()).is_err());
+
+        let revealed = Fairness::from_revealed(&hex::encode(fairness.get_server_seed()), client_seeds)?;
+        let mut deck = Deck::default();
+        deck.shuffle(&mut revealed.get_shuffler());
+
+        assert_eq!(revealed.get_commitment(), fairness.get_commitment());
+        assert_eq!(deck.get_cards(), game.deck.get_cards());
+        assert_eq!(find_first_flop(&deck.get_cards()[..51]), game.flopped_at);
+        Ok(())
+    }
+
+    #[test]
+    fn details() -> Result<()> {
+        let wager_map = HashMap::from([(Player::new("player1".to_string()), vec![Wager::new(0, AtFlop(1), Money::from_minor(10))?])]);
+        let mut game = Fts::new(FtsConfig::new(wager_map, "house".to_string(), CurrencyRules::default(), None)?, Shuffler::from_seed([5; 32]))?;
+
+        let details = serde_json::to_value(game.get_details())?;
+        assert_eq!(details["kind"], "Fts");
+        assert_eq!(details["state"], serde_json::json!({ "Game": "Setup" }));
+        assert_eq!(details["config"]["house_id"], "house");
+        assert_eq!(details["config"]["wagers"]["player1"][0]["wager_type"], serde_json::json!({ "AtFlop": 1 }));
+        assert_eq!(details["config"]["odds"]["full_deck"], 17);
+        assert!(details.get("flops").is_none());
+
+        game.transition(Transition::Game(Start))?;
+
+        let details = serde_json::to_value(game.get_details())?;
+        assert_eq!(details["flops"].as_array().map(Vec::len), Some(1));
+        assert_eq!(details["flops"][0].as_array().map(Vec::len), Some(3));
+        assert_eq!(details["flopped_at"], serde_json::to_value(game.flopped_at)?);
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_round_trip() -> Result<()> {
+        let wager_map = HashMap::from([
+            (Player::new("player1".to_string()), vec![Wager::new(0, FullDeck, Money::from_minor(10))?, Wager::new(1, AtFlop(3), Money::from_minor(5))?]),
+            (Player::new("player2".to_string()), vec![Wager::new(2, FlopRange(0, 4), Money::from_minor(20))?])
+        ]);
+        let odds: Odds = serde_json::from_str(r#"{ "full_deck": 20, "at_flop": 12, "flop_range": 3 }"#)?;
+        let mut game = Fts::new(FtsConfig::new(wager_map, "house".to_string(), CurrencyRules::default(), Some(odds))?, Shuffler::from_entropy())?;
+
+        // restored before the shuffle, the game shuffles the same way
+        let mut restored = crate::game::restore(game.snapshot()?)?;
+        game.transition(Transition::Game(Start))?;
+        restored.transition(Transition::Game(Start))?;
+        assert_eq!(sorted_payouts(restored.as_ref())?, sorted_payouts(&game)?);
+
+        let restored = crate::game::restore(serde_json::from_str(&serde_json::to_string(&game.snapshot()?)?)?)?;
+        assert_eq!(restored.get_state(), &State::Game(Ended));
+        assert_eq!(restored.get_deck_pool()[0].get_cards(), game.deck.get_cards());
+        assert_eq!(restored.get_deck_pool()[0].get_dealt_cards(), game.deck.get_dealt_cards());
+        assert_eq!(restored.get_seed(), game.get_seed());
+        assert_eq!(sorted_payouts(restored.as_ref())?, sorted_payouts(&game)?);
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_snapshot_version() -> Result<()> {
+        let wager_map = HashMap::from([(Player::new("player1".to_string()), vec![Wager::new(0, FullDeck, Money::from_minor(10))?])]);
+        let game = Fts::new(FtsConfig::new(wager_map, "house".to_string(), CurrencyRules::default(), None)?, Shuffler::from_entropy())?;
+
+        let mut snapshot = serde_json::to_value(game.snapshot()?)?;
+        snapshot["version"] = serde_json::json!(crate::game::SNAPSHOT_VERSION + 1);
+
+        assert!(crate::game::restore(serde_json::from_value(snapshot)?).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use thiserror::Error;
+use crate::config::fts::{FtsWagerType, Odds, MAX_ODDS, MIN_ODDS};
+use crate::config::WagerMap;
+use crate::deck::Deck;
+use crate::game::fts::get_multiplier;
+
+#[derive(Error, Debug)]
+enum PricingError {
+    #[error("Target house edge must be at least 0 and below 1, got {0}")]
+    HouseEdgeOutOfBounds(f64),
+
+    #[error("Wagers that can never win cannot be priced")]
+    NeverWins
+}
+
+// Wagers sharing the same odds are priced together
+#[derive(Eq, PartialEq, Hash)]
+enum OddsSlot {
+    FullDeck,
+    AtFlop,
+    FlopRange(u8, u8)
+}
+
+impl From<&FtsWagerType> for OddsSlot {
+    fn from(wager_type: &FtsWagerType) -> Self {
+        match wager_type {
+            FtsWagerType::FullDeck => OddsSlot::FullDeck,
+            FtsWagerType::AtFlop(_) => OddsSlot::AtFlop,
+            FtsWagerType::FlopRange(flop_start, flop_end) => OddsSlot::FlopRange(*flop_start, *flop_end)
+        }
+    }
+}
+
+// Probability of the first same suit flop happening at each flop, taken over every possible order of the deck
+#[derive(Debug)]
+pub struct FlopDistribution {
+    flopped_at: Vec<f64>,
+    no_flop: f64
+}
+
+impl FlopDistribution {
+    // Every possible value of `flopped_at` along with its probability
+    pub fn outcomes(&self) -> impl Iterator<Item = (Option<u8>, f64)> + '_ {
+        self.flopped_at.iter()
+            .enumerate()
+            .map(|(flop, probability)| (Some(flop as u8), *probability))
+            .chain(std::iter::once((None, self.no_flop)))
+    }
+}
+
+// Win probability and expectation of a unit wager. The house edge is the expected house win over the most
+// the wager can lose, which is what the player puts at risk.
+#[derive(Debug)]
+pub struct WagerOdds {
+    pub win_probability: f64,
+    pub expected_value: f64,
+    pub house_edge: f64,
+    pub max_loss: i32
+}
+
+// Whole unit odds and the house edge they actually give
+#[derive(Debug)]
+pub struct Pricing {
+    pub odds: i32,
+    pub house_edge: f64
+}
+
+// Distribution of `flopped_at` when dealing `flops` flops out of the shuffled deck. Only the suit of each card
+// matters, so the remaining deck is tracked as its sorted suit counts while flops are drawn one at a time.
+pub fn flop_distribution(deck: &Deck, flops: u8) -> FlopDistribution {
+    let mut suit_counts: Vec<u32> = Vec::new();
+
+    for card in deck.get_cards() {
+        let suit = usize::from(u8::from(card.get_suit()));
+
+        if suit_counts.len() <= suit {
+            suit_counts.resize(suit + 1, 0);
+        }
+
+        suit_counts[suit] += 1;
+    }
+
+    suit_counts.sort_unstable_by(|a, b| b.cmp(a));
+
+    // probability of every remaining deck composition reachable without a flop so far
+    let mut no_flop: HashMap<Vec<u32>, f64> = HashMap::from([(suit_counts, 1.0)]);
+    let mut flopped_at = Vec::with_capacity(usize::from(flops));
+
+    for _ in 0..flops {
+        let mut flopped = 0.0;
+        let mut next: HashMap<Vec<u32>, f64> = HashMap::new();
+
+        for (counts, probability) in &no_flop {
+            draw_flop(counts, *probability, &mut flopped, &mut next);
+        }
+
+        flopped_at.push(flopped);
+        no_flop = next;
+    }
+
+    FlopDistribution { flopped_at, no_flop: no_flop.values().sum() }
+}
+
+// Draws 3 cards one at a time from `counts`, adding the same suit outcomes to `flopped` and the others to `next`
+fn draw_flop(counts: &[u32], probability: f64, flopped: &mut f64, next: &mut HashMap<Vec<u32>, f64>) {
+    let total: u32 = counts.iter().sum();
+
+    if total < 3 {
+        // not enough cards left to deal the flop, nothing can happen anymore
+        *next.entry(counts.to_vec()).or_insert(0.0) += probability;
+        return;
+    }
+
+    let mut remaining = counts.to_vec();
+
+    for first in 0..counts.len() {
+        let p_first = f64::from(remaining[first]) / f64::from(total);
+        if p_first == 0.0 { continue; }
+        remaining[first] -= 1;
+
+        for second in 0..counts.len() {
+            let p_second = f64::from(remaining[second]) / f64::from(total - 1);
+            if p_second == 0.0 { continue; }
+            remaining[second] -= 1;
+
+            for third in 0..counts.len() {
+                let p_third = f64::from(remaining[third]) / f64::from(total - 2);
+                if p_third == 0.0 { continue; }
+
+                let p_flop = probability * p_first * p_second * p_third;
+
+                if first == second && second == third {
+                    *flopped += p_flop;
+                } else {
+                    remaining[third] -= 1;
+
+                    let mut key = remaining.clone();
+                    key.sort_unstable_by(|a, b| b.cmp(a));
+                    *next.entry(key).or_insert(0.0) += p_flop;
+
+                    remaining[third] += 1;
+                }
+            }
+
+            remaining[second] += 1;
+        }
+
+        remaining[first] += 1;
+    }
+}
+
+// Odds of a unit wager under `odds` when the flops are distributed as `distribution`, `flop_count` being the
+// number of flops in a full deck
+pub fn wager_odds(wager_type: &FtsWagerType, odds: &Odds, distribution: &FlopDistribution, flop_count: u8) -> WagerOdds {
+    let mut win_probability = 0.0;
+    let mut expected_value = 0.0;
+    let mut max_loss = 0;
+
+    for (flopped_at, probability) in distribution.outcomes() {
+        let multiplier = get_multiplier(wager_type, odds, flopped_at, flop_count);
+
+        if multiplier > 0 {
+            win_probability += probability;
+        }
+
+        expected_value += probability * f64::from(multiplier);
+        max_loss = std::cmp::max(max_loss, -multiplier);
+    }
+
+    let house_edge = if max_loss > 0 { -expected_value / f64::from(max_loss) } else { 0.0 };
+
+    WagerOdds { win_probability, expected_value, house_edge, max_loss }
+}
+
+// Prices wagers sharing the same odds, each weighted by its amount. Odds are rounded down so the house gets at
+// least `house_edge` unless the odds bounds are hit, the edge actually reached is returned with them.
+pub fn price(wagers: &[(&FtsWagerType, f64)], house_edge: f64, distribution: &FlopDistribution, flop_count: u8) -> Result<Pricing> {
+    if !(0.0..1.0).contains(&house_edge) {
+        return Err(anyhow!(PricingError::HouseEdgeOutOfBounds(house_edge)));
+    }
+
+    // the expected result and the amount at risk of the whole group when paying `odds`
+    let evaluate = |odds_value: i32| {
+        let mut odds = Odds::default();
+        wagers.iter().for_each(|(wager_type, _)| odds.set_odds(wager_type, odds_value));
+
+        wagers.iter()
+            .map(|(wager_type, weight)| {
+                let wager_odds = wager_odds(wager_type, &odds, distribution, flop_count);
+                (wager_odds.expected_value * weight, f64::from(wager_odds.max_loss) * weight)
+            })
+            .fold((0.0, 0.0), |(ev, stake), (wager_ev, wager_stake)| (ev + wager_ev, stake + wager_stake))
+    };
+
+    // the expectation is linear in the odds, with high odds every win is positive and only losses are at risk
+    let (high_ev, stake) = evaluate(MAX_ODDS);
+    let slope = high_ev - evaluate(MAX_ODDS - 1).0;
+
+    if slope <= 0.0 || stake <= 0.0 {
+        return Err(anyhow!(PricingError::NeverWins));
+    }
+
+    let exact_odds = MAX_ODDS as f64 + (-house_edge * stake - high_ev) / slope;
+    let odds = (exact_odds.floor() as i32).clamp(MIN_ODDS, MAX_ODDS);
+
+    let (ev, stake) = evaluate(odds);
+
+    Ok(Pricing { odds, house_edge: -ev / stake })
+}
+
+// Odds for every wager of a game config giving the house `house_edge`, over a full single deck
+pub fn auto_odds(wagers: &WagerMap<FtsWagerType>, house_edge: f64) -> Result<Odds> {
+    let deck = Deck::default();
+    let flop_count = (deck.len() / 3) as u8;
+    let distribution = flop_distribution(&deck, flop_count);
+
+    let mut slots: HashMap<OddsSlot, Vec<(&FtsWagerType, f64)>> = HashMap::new();
+
+    for wager in wagers.values().flat_map(|wagers| wagers.iter()) {
+        slots.entry(OddsSlot::from(wager.get_wager_type()))
+            .or_default()
+            .push((wager.get_wager_type(), wager.amount.to_f64()));
+    }
+
+    let mut odds = Odds::default();
+
+    for slot_wagers in slots.values() {
+        let pricing = price(slot_wagers, house_edge, &distribution, flop_count)?;
+        slot_wagers.iter().for_each(|(wager_type, _)| odds.set_odds(wager_type, pricing.odds));
+    }
+
+    Ok(odds)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::Card;
+    use crate::card::Suit::{Hearts, Spades};
+    use crate::config::fts::FtsWagerType::{AtFlop, FlopRange, FullDeck};
+    use crate::money::Money;
+    use super::*;
+
+    const EPSILON: f64 = 1e-12;
+
+    impl FlopDistribution {
+        pub fn get_flopped_at(&self, flop: u8) -> f64 {
+            self.flopped_at.get(usize::from(flop)).copied().unwrap_or(0.0)
+        }
+
+        pub fn get_no_flop(&self) -> f64 {
+            self.no_flop
+        }
+
+        pub fn get_flop_count(&self) -> u8 {
+            self.flopped_at.len() as u8
+        }
+    }
+
+    fn assert_distribution(distribution: &FlopDistribution) {
+        let total: f64 = distribution.outcomes().map(|(_, probability)| probability).sum();
+        assert!((total - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn first_flop_of_single_deck() {
+        let distribution = flop_distribution(&Deck::default(), 1);
+
+        // 4 suits * C(13, 3) / C(52, 3)
+        assert!((distribution.get_flopped_at(0) - 4.0 * 286.0 / 22100.0).abs() < EPSILON);
+        assert_distribution(&distribution);
+    }
+
+    #[test]
+    fn full_single_deck() {
+        let distribution = flop_distribution(&Deck::default(), 17);
+
+        assert_eq!(distribution.get_flop_count(), 17);
+        assert_distribution(&distribution);
+
+        // it gets harder to avoid a flop as the deck runs out
+        assert!(distribution.get_no_flop() > 0.0);
+        assert!(distribution.get_no_flop() < 0.5);
+    }
+
+    #[test]
+    fn small_deck() {
+        let deck = Deck::from_cards(
+            [Hearts, Hearts, Hearts, Spades, Spades, Spades].into_iter()
+                .map(|suit| Card::new(2, suit).unwrap())
+                .collect()
+        );
+        let distribution = flop_distribution(&deck, 2);
+
+        // the second flop can only be mixed when the first one is
+        assert!((distribution.get_flopped_at(0) - 0.1).abs() < EPSILON);
+        assert_eq!(distribution.get_flopped_at(1), 0.0);
+        assert!((distribution.get_no_flop() - 0.9).abs() < EPSILON);
+    }
+
+    #[test]
+    fn at_flop_odds() {
+        let distribution = flop_distribution(&Deck::default(), 1);
+        let odds = Odds::default();
+        let wager_odds = wager_odds(&AtFlop(0), &odds, &distribution, 17);
+        let p = distribution.get_flopped_at(0);
+
+        assert!((wager_odds.win_probability - p).abs() < EPSILON);
+        assert!((wager_odds.expected_value - (17.0 * p - (1.0 - p))).abs() < EPSILON);
+        assert!((wager_odds.house_edge + wager_odds.expected_value).abs() < EPSILON);
+    }
+
+    #[test]
+    fn house_edge_is_relative_to_max_loss() {
+        let distribution = flop_distribution(&Deck::default(), 17);
+        let odds = Odds::default();
+
+        let full_deck = wager_odds(&FullDeck, &odds, &distribution, 17);
+        assert!((full_deck.house_edge + full_deck.expected_value / 17.0).abs() < EPSILON);
+
+        let range = wager_odds(&FlopRange(2, 4), &odds, &distribution, 17);
+        assert!((range.house_edge + range.expected_value / 3.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn price_single_wager() -> Result<()> {
+        let distribution = flop_distribution(&Deck::default(), 17);
+
+        for wager_type in [FullDeck, AtFlop(0), AtFlop(5), FlopRange(0, 0), FlopRange(2, 6)] {
+            let pricing = price(&[(&wager_type, 1.0)], 0.03, &distribution, 17)?;
+
+            let mut odds = Odds::default();
+            odds.set_odds(&wager_type, pricing.odds);
+            let edge = wager_odds(&wager_type, &odds, &distribution, 17).house_edge;
+
+            // the rounded odds give at least the target, one more unit would give less
+            assert!((pricing.house_edge - edge).abs() < EPSILON);
+            assert!(pricing.house_edge >= 0.03);
+
+            odds.set_odds(&wager_type, pricing.odds + 1);
+            assert!(wager_odds(&wager_type, &odds, &distribution, 17).house_edge < 0.03);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn price_invalid_edge() {
+        let distribution = flop_distribution(&Deck::default(), 17);
+
+        assert!(price(&[(&FullDeck, 1.0)], -0.01, &distribution, 17).is_err());
+        assert!(price(&[(&FullDeck, 1.0)], 1.0, &distribution, 17).is_err());
+    }
+
+    #[test]
+    fn auto_odds_for_config() -> Result<()> {
+        use std::collections::HashMap;
+        use crate::player::Player;
+        use crate::wager::Wager;
+
+        let wagers: WagerMap<FtsWagerType> = HashMap::from([
+            (Player::new("player1".to_string()), vec![Wager::new(0, FullDeck, Money::from_minor(10))?, Wager::new(1, FlopRange(0, 3), Money::from_minor(10))?]),
+            (Player::new("player2".to_string()), vec![Wager::new(2, AtFlop(1), Money::from_minor(10))?, Wager::new(3, AtFlop(4), Money::from_minor(30))?])
+        ]);
+
+        let odds = auto_odds(&wagers, 0.05)?;
+        let distribution = flop_distribution(&Deck::default(), 17);
+
+        assert!(wager_odds(&FullDeck, &odds, &distribution, 17).house_edge >= 0.05);
+        assert!(wager_odds(&FlopRange(0, 3), &odds, &distribution, 17).house_edge >= 0.05);
+
+        // at flop wagers share their odds, the edge is reached over both of them
+        let at_flop_ev = 10.0 * wager_odds(&AtFlop(1), &odds, &distribution, 17).expected_value
+            + 30.0 * wager_odds(&AtFlop(4), &odds, &distribution, 17).expected_value;
+        assert!(-at_flop_ev / 40.0 >= 0.05);
+        Ok(())
+    }
+}
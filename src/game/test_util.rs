@@ -0,0 +1,18 @@
+use anyhow::Result;
+use crate::game::Game;
+use crate::payout::Payout;
+
+// Net amount a player gets over all of their payout lines
+pub(crate) fn amount_of(payouts: &[Payout], player_id: &str) -> i64 {
+    payouts.iter()
+        .filter(|payout| payout.get_player_id() == player_id)
+        .map(|payout| payout.get_amount().get_minor())
+        .sum()
+}
+
+// payout order follows the wager map, which is not kept across a restore
+pub(crate) fn sorted_payouts(game: &dyn Game) -> Result<Vec<String>> {
+    let mut payouts = game.get_payout()?.iter().map(serde_json::to_string).collect::<Result<Vec<String>, _>>()?;
+    payouts.sort();
+    Ok(payouts)
+}
\ No newline at end of file
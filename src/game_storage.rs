@@ -1,30 +1,284 @@
-use std::collections::HashMap;
+mod file;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use anyhow::Result;
 use uuid::Uuid;
-use crate::game::Game;
+use crate::fairness::Fairness;
+use crate::game;
+use crate::game::{Game, GameType, WagerChange};
+use crate::game::Error::NotFound;
+use crate::transition::Transition;
+
+pub use file::FileStorage;
+
+// Ids are spread over this many independently locked maps
+const SHARD_COUNT: usize = 16;
+
+// Each game sits behind its own lock, holding it never blocks other games
+pub type GameRef = Arc<Mutex<StoredGame>>;
+
+// Games only change through the storage so that every backend can record the change
+pub trait GameStorage: Send + Sync {
+    fn create_game(&self, game_type: GameType, config: &str, fairness: Fairness) -> Result<String>;
+    fn get_game(&self, id: &str) -> Option<GameRef>;
+    fn transition_game(&self, id: &str, transition: Transition) -> Result<()>;
+
+    // Client seeds are merged into the fairness record of a game in setup and reseed its shuffler
+    fn add_client_seeds(&self, id: &str, client_seeds: BTreeMap<String, String>) -> Result<()>;
+
+    // `admit` sees the changed game and can still refuse the change, the game is then put back as it was
+    fn change_wagers(&self, id: &str, change: WagerChange, admit: &mut dyn FnMut(&dyn Game) -> Result<()>) -> Result<()>;
+    fn list_games(&self) -> Vec<(String, GameRef)>;
+    fn game_count(&self) -> usize;
+
+    // Removes the game if `condition` accepts it, the condition is checked under the game lock
+    fn remove_game_if(&self, id: &str, condition: &mut dyn FnMut(&StoredGame) -> Result<bool>) -> Result<bool>;
+
+    fn remove_game(&self, id: &str) -> Result<()> {
+        self.remove_game_if(id, &mut |_| Ok(true))?;
+        Ok(())
+    }
+}
+
+pub struct StoredGame {
+    game: Box<dyn Game>,
+    fairness: Fairness,
+    removed: bool,      // set under the game lock, callers still holding a reference must not change it anymore
+    touched_at: Instant // creation or last accepted transition, games loaded from a journal count from the load
+}
+
+impl StoredGame {
+    pub fn get_game(&self) -> &dyn Game {
+        self.game.as_ref()
+    }
+
+    pub fn get_fairness(&self) -> &Fairness {
+        &self.fairness
+    }
+
+    pub fn get_touched_at(&self) -> Instant {
+        self.touched_at
+    }
+}
+
+// The fairness record only changes once the game took the new shuffler
+fn apply_client_seeds(stored: &mut StoredGame, client_seeds: BTreeMap<String, String>) -> Result<()> {
+    let fairness = stored.fairness.with_client_seeds(client_seeds);
+    stored.game.reseed(fairness.get_shuffler())?;
+    stored.fairness = fairness;
+    Ok(())
+}
+
+// The game goes back to its snapshot when the change or its admission fails
+fn apply_wager_change(stored: &mut StoredGame, change: WagerChange, admit: &mut dyn FnMut(&dyn Game) -> Result<()>) -> Result<()> {
+    let snapshot = stored.game.snapshot()?;
+
+    if let Err(e) = stored.game.change_wagers(change).and_then(|_| admit(stored.game.as_ref())) {
+        stored.game = game::restore(snapshot)?;
+        return Err(e);
+    }
+
+    Ok(())
+}
 
-pub struct GameStorage {
-    map: HashMap<String, Box<dyn Game>>
+pub struct MemoryStorage {
+    shards: Vec<RwLock<HashMap<String, GameRef>>>
 }
 
-impl GameStorage {
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        MemoryStorage::new()
+    }
+}
+
+impl MemoryStorage {
     pub fn new() -> Self {
-        GameStorage {
-            map: HashMap::new()
+        MemoryStorage {
+            shards: (0..SHARD_COUNT).map(|_| RwLock::new(HashMap::new())).collect()
         }
     }
 
-    pub fn insert_game(&mut self, game: Box<dyn Game>) -> String {
+    fn shard(&self, id: &str) -> &RwLock<HashMap<String, GameRef>> {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARD_COUNT]
+    }
+
+    fn insert_game(&self, id: String, game: Box<dyn Game>, fairness: Fairness) {
+        let stored = StoredGame { game, fairness, removed: false, touched_at: Instant::now() };
+        self.shard(&id).write().unwrap().insert(id, Arc::new(Mutex::new(stored)));
+    }
+
+    // Runs `f` on a live game while holding its lock
+    fn with_game<R>(&self, id: &str, f: impl FnOnce(&mut StoredGame) -> Result<R>) -> Result<R> {
+        let game_ref = self.get_game(id).ok_or(NotFound(id.to_string()))?;
+        let mut stored = game_ref.lock().unwrap();
+
+        if stored.removed {
+            return Err(NotFound(id.to_string()).into());
+        }
+
+        let result = f(&mut stored)?;
+        stored.touched_at = Instant::now();
+        Ok(result)
+    }
+}
+
+impl GameStorage for MemoryStorage {
+    fn create_game(&self, game_type: GameType, config: &str, fairness: Fairness) -> Result<String> {
         let key = Uuid::new_v4().to_string();
-        self.map.insert(key.clone(), game);
-        key
+        let game = game::create_game(&game_type, config, fairness.get_shuffler())?;
+        self.insert_game(key.clone(), game, fairness);
+        Ok(key)
+    }
+
+    fn get_game(&self, id: &str) -> Option<GameRef> {
+        self.shard(id).read().unwrap().get(id).cloned()
     }
 
-    pub fn get_game(&mut self, id: &str) -> Option<&mut dyn Game> {
-        let mut game = self.map.get_mut(id)?;
-        Some(game.as_mut())
+    fn transition_game(&self, id: &str, transition: Transition) -> Result<()> {
+        self.with_game(id, |stored| stored.game.transition(transition))
     }
 
-    pub fn remove_game(&mut self, id: &str) {
-        self.map.remove(id);
+    fn add_client_seeds(&self, id: &str, client_seeds: BTreeMap<String, String>) -> Result<()> {
+        self.with_game(id, |stored| apply_client_seeds(stored, client_seeds))
     }
-}
\ No newline at end of file
+
+    fn change_wagers(&self, id: &str, change: WagerChange, admit: &mut dyn FnMut(&dyn Game) -> Result<()>) -> Result<()> {
+        self.with_game(id, |stored| apply_wager_change(stored, change, admit))
+    }
+
+    fn list_games(&self) -> Vec<(String, GameRef)> {
+        self.shards.iter()
+            .flat_map(|shard| {
+                shard.read().unwrap().iter().map(|(id, game_ref)| (id.clone(), game_ref.clone())).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn game_count(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().unwrap().len()).sum()
+    }
+
+    // Takes the game lock before the shard lock, nothing else waits for a game lock while holding a shard
+    fn remove_game_if(&self, id: &str, condition: &mut dyn FnMut(&StoredGame) -> Result<bool>) -> Result<bool> {
+        let game_ref = self.get_game(id).ok_or(NotFound(id.to_string()))?;
+        let mut stored = game_ref.lock().unwrap();
+
+        if stored.removed {
+            return Err(NotFound(id.to_string()).into());
+        }
+
+        if !condition(&stored)? {
+            return Ok(false);
+        }
+
+        self.shard(id).write().unwrap().remove(id);
+        stored.removed = true;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use crate::state::GameState::{Ended, Setup};
+    use crate::state::State;
+    use crate::transition::GameTransition::Start;
+    use super::*;
+
+    const FTS_CONFIG: &str = r#"{ "wagers": { "player1": [{ "id": 0, "wager_type": "FullDeck", "amount": 10 }] }, "house_id": "house" }"#;
+
+    fn create_fts(storage: &MemoryStorage) -> Result<String> {
+        storage.create_game(GameType::Fts, FTS_CONFIG, Fairness::new())
+    }
+
+    #[test]
+    fn locked_game_does_not_block_others() -> Result<()> {
+        let storage = Arc::new(MemoryStorage::new());
+        let blocked_id = create_fts(&storage)?;
+        let free_id = create_fts(&storage)?;
+
+        let blocked = storage.get_game(&blocked_id).unwrap();
+        let _guard = blocked.lock().unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let worker_storage = storage.clone();
+        thread::spawn(move || {
+            sender.send(worker_storage.transition_game(&free_id, Transition::Game(Start)).is_ok()).unwrap();
+        });
+
+        assert!(receiver.recv_timeout(Duration::from_secs(5))?);
+        Ok(())
+    }
+
+    fn parallel_load(storage: Arc<dyn GameStorage>) -> Result<()> {
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let storage = storage.clone();
+                thread::spawn(move || -> Result<Vec<String>> {
+                    let mut ids = Vec::new();
+
+                    for _ in 0..50 {
+                        let id = storage.create_game(GameType::Fts, FTS_CONFIG, Fairness::new())?;
+                        storage.transition_game(&id, Transition::Game(Start))?;
+                        storage.get_game(&id).unwrap().lock().unwrap().get_game().get_payout()?;
+                        ids.push(id);
+                    }
+
+                    Ok(ids)
+                })
+            })
+            .collect();
+
+        let mut ids = Vec::new();
+        for worker in workers {
+            ids.extend(worker.join().unwrap()?);
+        }
+
+        assert_eq!(ids.len(), 400);
+        assert_eq!(storage.game_count(), 400);
+        for id in ids {
+            assert_eq!(storage.get_game(&id).unwrap().lock().unwrap().get_game().get_state(), &State::Game(Ended));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parallel_memory_load() -> Result<()> {
+        parallel_load(Arc::new(MemoryStorage::new()))
+    }
+
+    // Concurrent appends share their syncs, every one of them is still in the journal
+    #[test]
+    fn parallel_file_load() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("zsonkorp-{}.jsonl", Uuid::new_v4()));
+        let result = parallel_load(Arc::new(FileStorage::open(&path)?))
+            .and_then(|_| FileStorage::open(&path))
+            .map(|storage| storage.game_count());
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(result?, 400);
+        Ok(())
+    }
+
+    #[test]
+    fn removed_game_is_not_transitioned() -> Result<()> {
+        let storage = MemoryStorage::new();
+        let id = create_fts(&storage)?;
+        let held = storage.get_game(&id).unwrap();
+
+        storage.remove_game(&id)?;
+
+        assert!(storage.get_game(&id).is_none());
+        assert!(storage.transition_game(&id, Transition::Game(Start)).is_err());
+        assert_eq!(held.lock().unwrap().get_game().get_state(), &State::Game(Setup));
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::fairness::Fairness;
+use crate::game;
+use crate::journal;
+use crate::game::{Game, GameType, Snapshot, WagerChange};
+use crate::game_storage::{GameRef, GameStorage, MemoryStorage, StoredGame};
+use crate::transition::Transition;
+
+// One JSON line per change. Created games are rebuilt from their config and seeds, which gives back the same
+// deck order, then their transitions are replayed. Restored games start from a snapshot instead.
+#[derive(Serialize, Deserialize)]
+enum Record {
+    Created {
+        id: String,
+        kind: GameType,
+        config: String,
+        server_seed: String,
+        client_seeds: BTreeMap<String, String>
+    },
+    Restored {
+        id: String,
+        snapshot: Snapshot,
+        server_seed: String,
+        client_seeds: BTreeMap<String, String>
+    },
+    Transition {
+        id: String,
+        transition: Transition
+    },
+    WagerChange {
+        id: String,
+        change: WagerChange
+    },
+    ClientSeeds {
+        id: String,
+        client_seeds: BTreeMap<String, String>
+    },
+    Removed {
+        id: String
+    }
+}
+
+// Keeps games in memory and appends every change to a journal file. The journal is replayed when opened, then
+// compacted to a single snapshot per live game.
+pub struct FileStorage {
+    games: MemoryStorage,
+    journal: Mutex<Journal>,
+    synced: Condvar
+}
+
+// Records are written under the journal lock but synced outside of it. Appends made while a sync is running wait
+// for the next one, which covers all of them at once.
+struct Journal {
+    file: File,
+    written: u64, // records written so far
+    synced: u64,  // records known to be on disk
+    syncing: bool
+}
+
+impl FileStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut games = MemoryStorage::new();
+
+        for (line, record) in journal::read_records(&path)?.0 {
+            FileStorage::replay(&mut games, record)
+                .map_err(|e| anyhow!("Could not replay journal line {}: {}", line, e))?;
+        }
+
+        // compaction rewrites the journal, a dropped last line goes with it
+        let journal = FileStorage::compact(&games, &path)?;
+
+        let journal = Journal { file: journal, written: 0, synced: 0, syncing: false };
+        Ok(FileStorage { games, journal: Mutex::new(journal), synced: Condvar::new() })
+    }
+
+    fn replay(games: &mut MemoryStorage, record: Record) -> Result<()> {
+        match record {
+            Record::Created { id, kind, config, server_seed, client_seeds } => {
+                let fairness = Fairness::from_revealed(&server_seed, client_seeds)?;
+                games.insert_game(id, game::create_game(&kind, &config, fairness.get_shuffler())?, fairness);
+                Ok(())
+            },
+            Record::Restored { id, snapshot, server_seed, client_seeds } => {
+                games.insert_game(id, game::restore(snapshot)?, Fairness::from_revealed(&server_seed, client_seeds)?);
+                Ok(())
+            },
+            Record::Transition { id, transition } => games.transition_game(&id, transition),
+            Record::ClientSeeds { id, client_seeds } => games.add_client_seeds(&id, client_seeds),
+            Record::WagerChange { id, change } => games.change_wagers(&id, change, &mut |_| Ok(())),
+            Record::Removed { id } => games.remove_game(&id)
+        }
+    }
+
+    // Writes the snapshots next to the journal and swaps them in, returns the new journal opened for appending
+    fn compact(games: &MemoryStorage, path: &Path) -> Result<File> {
+        let compact_path = path.with_extension("compact");
+        let mut compacted = File::create(&compact_path)?;
+
+        for (id, game_ref) in games.list_games() {
+            let stored = game_ref.lock().unwrap();
+            let record = Record::Restored {
+                id,
+                snapshot: stored.game.snapshot()?,
+                server_seed: hex::encode(stored.fairness.get_server_seed()),
+                client_seeds: stored.fairness.get_client_seeds().clone()
+            };
+
+            writeln!(compacted, "{}", serde_json::to_string(&record)?)?;
+        }
+
+        compacted.sync_all()?;
+        fs::rename(&compact_path, path)?;
+
+        // the rename only survives a crash once the directory holding the journal is synced
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new(".")
+        };
+        File::open(dir)?.sync_all()?;
+
+        Ok(OpenOptions::new().append(true).open(path)?)
+    }
+
+    // Works the change out on a copy of the game, which only replaces the game once its record is written
+    fn staged(&self, stored: &mut StoredGame, record: &str, change: impl FnOnce(&mut dyn Game) -> Result<()>) -> Result<()> {
+        let mut game = game::restore(stored.game.snapshot()?)?;
+        change(game.as_mut())?;
+
+        self.append(record)?;
+        stored.game = game;
+        Ok(())
+    }
+
+    // Returns once the record is on disk, one caller syncs for every record written by then
+    fn append(&self, record: &str) -> Result<()> {
+        let mut journal = self.journal.lock().unwrap();
+        writeln!(journal.file, "{}", record)?;
+        journal.written += 1;
+        let sequence = journal.written;
+
+        while journal.synced < sequence {
+            if journal.syncing {
+                journal = self.synced.wait(journal).unwrap();
+                continue;
+            }
+
+            let target = journal.written;
+            let file = journal.file.try_clone()?;
+            journal.syncing = true;
+            drop(journal);
+
+            let result = file.sync_data();
+
+            journal = self.journal.lock().unwrap();
+            journal.syncing = false;
+            if result.is_ok() {
+                journal.synced = target;
+            }
+            self.synced.notify_all();
+            result?;
+        }
+
+        Ok(())
+    }
+}
+
+impl GameStorage for FileStorage {
+    fn create_game(&self, game_type: GameType, config: &str, fairness: Fairness) -> Result<String> {
+        let id = Uuid::new_v4().to_string();
+
+        let record = serde_json::to_string(&Record::Created {
+            id: id.clone(),
+            kind: game_type,
+            config: config.to_string(),
+            server_seed: hex::encode(fairness.get_server_seed()),
+            client_seeds: fairness.get_client_seeds().clone()
+        })?;
+
+        let game = game::create_game(&game_type, config, fairness.get_shuffler())?;
+        self.append(&record)?;
+        self.games.insert_game(id.clone(), game, fairness);
+        Ok(id)
+    }
+
+    fn get_game(&self, id: &str) -> Option<GameRef> {
+        self.games.get_game(id)
+    }
+
+    // Only transitions the game accepted are recorded, the game lock keeps its records in order
+    fn transition_game(&self, id: &str, transition: Transition) -> Result<()> {
+        let record = serde_json::to_string(&Record::Transition { id: id.to_string(), transition: transition.clone() })?;
+
+        self.games.with_game(id, |stored| self.staged(stored, &record, |game| game.transition(transition)))
+    }
+
+    fn add_client_seeds(&self, id: &str, client_seeds: BTreeMap<String, String>) -> Result<()> {
+        let record = serde_json::to_string(&Record::ClientSeeds { id: id.to_string(), client_seeds: client_seeds.clone() })?;
+
+        self.games.with_game(id, |stored| {
+            let fairness = stored.fairness.with_client_seeds(client_seeds);
+            self.staged(stored, &record, |game| game.reseed(fairness.get_shuffler()))?;
+            stored.fairness = fairness;
+            Ok(())
+        })
+    }
+
+    // Worked out on a copy and recorded once admitted. A change that cannot be recorded is admitted back to the
+    // game as it was, which puts its escrow back.
+    fn change_wagers(&self, id: &str, change: WagerChange, admit: &mut dyn FnMut(&dyn Game) -> Result<()>) -> Result<()> {
+        let record = serde_json::to_string(&Record::WagerChange { id: id.to_string(), change: change.clone() })?;
+
+        self.games.with_game(id, |stored| {
+            let mut game = game::restore(stored.game.snapshot()?)?;
+            game.change_wagers(change)?;
+            admit(game.as_ref())?;
+
+            if let Err(e) = self.append(&record) {
+                if let Err(compensation) = admit(stored.game.as_ref()) {
+                    eprintln!("Escrow of game {} left at the unrecorded wagers: {}", id, compensation);
+                }
+                return Err(e);
+            }
+
+            stored.game = game;
+            Ok(())
+        })
+    }
+
+    fn list_games(&self) -> Vec<(String, GameRef)> {
+        self.games.list_games()
+    }
+
+    fn game_count(&self) -> usize {
+        self.games.game_count()
+    }
+
+    // Recorded under the game lock before the game goes, later transitions are refused once the game is flagged
+    fn remove_game_if(&self, id: &str, condition: &mut dyn FnMut(&StoredGame) -> Result<bool>) -> Result<bool> {
+        let record = serde_json::to_string(&Record::Removed { id: id.to_string() })?;
+
+        self.games.remove_game_if(id, &mut |stored| {
+            if !condition(stored)? {
+                return Ok(false);
+            }
+
+            self.append(&record)?;
+            Ok(true)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::money::Money;
+    use crate::transition::{CtaTransition, GameTransition};
+    use super::*;
+
+    const FTS_CONFIG: &str = r#"{ "wagers": { "player1": [{ "id": 0, "wager_type": "FullDeck", "amount": 10 }] }, "house_id": "house" }"#;
+    const CTA_CONFIG: &str = r#"{ "wagers": { "player1": [{ "id": 0, "wager_type": "Forward", "amount": 10 }] }, "house_id": "house", "cuts": 2 }"#;
+
+    fn journal_path() -> PathBuf {
+        std::env::temp_dir().join(format!("zsonkorp-{}.jsonl", Uuid::new_v4()))
+    }
+
+    fn details(storage: &FileStorage, id: &str) -> Result<String> {
+        let game_ref = storage.get_game(id).ok_or(anyhow!("missing game"))?;
+        let stored = game_ref.lock().unwrap();
+        let game = stored.get_game();
+        Ok(format!("{} {}", serde_json::to_string(&game.get_details())?, serde_json::to_string(&game.get_payout()?)?))
+    }
+
+    #[test]
+    fn reload() -> Result<()> {
+        let path = journal_path();
+        let client_seeds = BTreeMap::from([("player1".to_string(), "lucky".to_string())]);
+
+        let (fts_id, cta_id, removed_id, before) = {
+            let storage = FileStorage::open(&path)?;
+
+            let fts_id = storage.create_game(GameType::Fts, FTS_CONFIG, Fairness::new())?;
+            storage.add_client_seeds(&fts_id, client_seeds.clone())?;
+            storage.transition_game(&fts_id, Transition::Game(GameTransition::Start))?;
+
+            let cta_id = storage.create_game(GameType::Cta, CTA_CONFIG, Fairness::new())?;
+            storage.transition_game(&cta_id, Transition::Game(GameTransition::Start))?;
+            storage.transition_game(&cta_id, Transition::Cta(CtaTransition::Cut { deck_index: 0, position: 20 }))?;
+
+            // rejected transitions are not recorded
+            assert!(storage.transition_game(&fts_id, Transition::Game(GameTransition::Start)).is_err());
+
+            let removed_id = storage.create_game(GameType::Fts, FTS_CONFIG, Fairness::new())?;
+            storage.remove_game(&removed_id)?;
+
+            (fts_id.clone(), cta_id.clone(), removed_id, [details(&storage, &fts_id)?, details(&storage, &cta_id)?])
+        };
+
+        // the first reopen compacts the journal to snapshots, the second one reads them back
+        let storage = FileStorage::open(&path)?;
+        assert_eq!([details(&storage, &fts_id)?, details(&storage, &cta_id)?], before);
+        assert_eq!(fs::read_to_string(&path)?.lines().count(), 2);
+
+        // the deciding cut is refused and not recorded when it ties, which every cut of a deck without aces does
+        let cut = [(0, 0..20), (1, 0..30)].into_iter()
+            .flat_map(|(deck_index, positions)| positions.map(move |position| (deck_index, position)))
+            .find(|(deck_index, position)| {
+                storage.transition_game(&cta_id, Transition::Cta(CtaTransition::Cut { deck_index: *deck_index, position: *position })).is_ok()
+            });
+        assert!(cut.is_some());
+        let after_cut = details(&storage, &cta_id)?;
+
+        let storage = FileStorage::open(&path)?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(details(&storage, &cta_id)?, after_cut);
+        assert_eq!(details(&storage, &fts_id)?, before[0]);
+        assert_eq!(storage.get_game(&fts_id).unwrap().lock().unwrap().get_fairness().get_client_seeds(), &client_seeds);
+        assert!(storage.get_game(&removed_id).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn wager_changes_are_replayed() -> Result<()> {
+        let path = journal_path();
+        let wager = serde_json::json!({ "id": 1, "wager_type": { "AtFlop": 0 }, "amount": 5 });
+
+        let (id, before) = {
+            let storage = FileStorage::open(&path)?;
+            let id = storage.create_game(GameType::Fts, FTS_CONFIG, Fairness::new())?;
+
+            storage.change_wagers(&id, WagerChange::Place { player_id: "player2".to_string(), wager: wager.clone() }, &mut |_| Ok(()))?;
+            storage.change_wagers(&id, WagerChange::Cancel { wager_id: 0 }, &mut |_| Ok(()))?;
+
+            // refused changes leave the game as it was and are not recorded
+            let refused = storage.change_wagers(&id, WagerChange::Amend { wager_id: 1, amount: Money::from_minor(50) }, &mut |_| Err(anyhow!("refused")));
+            assert!(refused.is_err());
+
+            (id.clone(), details(&storage, &id)?)
+        };
+
+        assert_eq!(fs::read_to_string(&path)?.lines().count(), 3);
+
+        let storage = FileStorage::open(&path)?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(details(&storage, &id)?, before);
+        assert!(before.contains("player2") && !before.contains("player1"));
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_journal() -> Result<()> {
+        let path = journal_path();
+        let id = FileStorage::open(&path)?.create_game(GameType::Fts, FTS_CONFIG, Fairness::new())?;
+
+        // a crash while appending leaves a torn last line, the games before it are still there
+        let complete = fs::read_to_string(&path)?;
+        fs::write(&path, format!("{}{{\"Transition\":{{\"id\":\"{}\"", complete, id))?;
+        assert!(FileStorage::open(&path)?.get_game(&id).is_some());
+        assert_eq!(fs::read_to_string(&path)?.lines().count(), 1);
+
+        // records that cannot be replayed are not dropped
+        fs::write(&path, "{\"Transition\":{\"id\":\"missing\",\"transition\":{\"Game\":\"Start\"}}}\n")?;
+        let result = FileStorage::open(&path);
+        fs::remove_file(&path)?;
+
+        assert!(result.is_err());
+        Ok(())
+    }
+}
@@ -1,67 +1,735 @@
+use std::cmp;
 use anyhow::{anyhow, Error};
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use axum::extract::rejection::QueryRejection;
+use axum::http::{HeaderMap, StatusCode};
 use axum::Json;
 use axum::response::{IntoResponse, Response};
 use serde::Deserialize;
+use serde_json::json;
+use crate::card::Card;
+use crate::deck::Deck;
+use crate::dto::{AmendWagerDto, BalanceDto, ClientSeedsDto, DeckLayoutDto, EntryDto, ErrorDto, FairnessDto, FtsPricingDto, GameStateDto, PlaceWagerDto, PricingDto, StatementDto, TransferDto, VerificationDto, VerifyDto};
 use crate::game;
 use crate::app_state::AppState;
 use crate::game::Error::NotFound;
-use crate::game::GameType::*;
-use crate::transition::Transition;
+use crate::game::WagerChange;
+use crate::game::fts::{find_first_flop, probability};
+use crate::fairness::Fairness;
+use crate::ledger;
+use crate::ledger::{Account, Ledger};
+use crate::transition::{GameTransition, Transition};
+use crate::validation::{pointer, ValidationErrors, Violations};
 
 #[derive(Deserialize)]
 pub struct GameTypeQuery {
     kind: game::GameType
 }
 
-pub struct AnyhowError(Error);
+// Wallet owners as they appear in ledger paths
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountKind {
+    Players,
+    Houses
+}
+
+// Failures are answered with a JSON ErrorDto, clients should match on its code rather than on the message
+pub struct ApiError {
+    status: StatusCode,
+    error_dto: ErrorDto
+}
+
+impl ApiError {
+    fn new(status: StatusCode, code: &'static str, message: String) -> Self {
+        ApiError { status, error_dto: ErrorDto { code, message, details: None } }
+    }
+}
 
-impl IntoResponse for AnyhowError {
+impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        (StatusCode::INTERNAL_SERVER_ERROR, self.0.to_string()).into_response()
+        (self.status, Json(self.error_dto)).into_response()
     }
 }
 
-impl<E> From<E> for AnyhowError where E: Into<Error>{
+impl<E> From<E> for ApiError where E: Into<Error>{
     fn from(value: E) -> Self {
-        Self(value.into())
+        let error = value.into();
+
+        if let Some(game_error) = error.downcast_ref::<game::Error>() {
+            let (status, code) = match game_error {
+                game::Error::Generic => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+                game::Error::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+                game::Error::Validation(_) => (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed"),
+                game::Error::InvalidTransition => (StatusCode::CONFLICT, "invalid_transition"),
+                game::Error::RejectedTransition(_) => (StatusCode::CONFLICT, "transition_rejected"),
+                game::Error::StorageFull(_) => (StatusCode::SERVICE_UNAVAILABLE, "storage_full"),
+                game::Error::BettingClosed => (StatusCode::CONFLICT, "betting_closed"),
+                game::Error::WagerNotFound(_) => (StatusCode::NOT_FOUND, "wager_not_found"),
+                game::Error::SeedingClosed => (StatusCode::CONFLICT, "seeding_closed")
+            };
+
+            return ApiError::new(status, code, game_error.to_string());
+        }
+
+        if let Some(ledger_error) = error.downcast_ref::<ledger::Error>() {
+            return match ledger_error {
+                ledger::Error::InsufficientFunds { account, currency, available, requested } => {
+                    let mut api_error = ApiError::new(StatusCode::CONFLICT, "insufficient_funds", ledger_error.to_string());
+                    api_error.error_dto.details = Some(json!({
+                        "account": account, "currency": currency, "available": available, "requested": requested
+                    }));
+                    api_error
+                },
+                ledger::Error::ExposureExceeded { account, currency, requested, exposure, limit } => {
+                    let mut api_error = ApiError::new(StatusCode::CONFLICT, "exposure_exceeded", ledger_error.to_string());
+                    api_error.error_dto.details = Some(json!({
+                        "account": account, "currency": currency, "requested": requested, "exposure": exposure, "limit": limit
+                    }));
+                    api_error
+                },
+                ledger::Error::Unbalanced(_) =>
+                    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", ledger_error.to_string())
+            };
+        }
+
+        if let Some(ValidationErrors(violations)) = error.downcast_ref::<ValidationErrors>() {
+            let mut api_error = ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed", error.to_string());
+            api_error.error_dto.details = Some(json!(violations));
+            return api_error;
+        }
+
+        if let Some(serde_error) = error.downcast_ref::<serde_json::Error>() {
+            let mut api_error = ApiError::new(StatusCode::BAD_REQUEST, "malformed_body", serde_error.to_string());
+            api_error.error_dto.details = Some(json!({ "line": serde_error.line(), "column": serde_error.column() }));
+            return api_error;
+        }
+
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", error.to_string())
+    }
+}
+
+fn validation(error: Error) -> ApiError {
+    ApiError::from(game::classify(error, game::Error::Validation))
+}
+
+fn ledger_of(state: &AppState) -> Result<&Ledger, ApiError> {
+    state.ledger.as_deref()
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "ledger_disabled", "This server keeps no ledger".to_string()))
+}
+
+// Operator requests carry the key the server was started with in this header
+const OPERATOR_KEY_HEADER: &str = "x-operator-key";
+
+fn operator_of(state: &AppState, headers: &HeaderMap, action: &str) -> Result<(), ApiError> {
+    let operator_key = headers.get(OPERATOR_KEY_HEADER).and_then(|key| key.to_str().ok());
+
+    match (&state.operator_key, operator_key) {
+        (Some(expected), Some(operator_key)) if expected.as_ref() == operator_key => Ok(()),
+        _ => Err(ApiError::new(StatusCode::FORBIDDEN, "operator_only", format!("Only operators may {}", action)))
+    }
+}
+
+fn account_of(kind: AccountKind, id: String) -> Account {
+    match kind {
+        AccountKind::Players => Account::Player(id),
+        AccountKind::Houses => Account::House(id)
     }
 }
 
+fn transfer_of(body: &str) -> Result<TransferDto, ApiError> {
+    let transfer_dto: TransferDto = serde_json::from_str(body)?;
+
+    let mut violations = Violations::new();
+    if !transfer_dto.amount.is_positive() {
+        violations.add(pointer(&["amount"]), format!("Amount must be positive, got {}", transfer_dto.amount));
+    }
+    violations.into_result()?;
+
+    Ok(transfer_dto)
+}
+
 pub async fn create_game(State(state): State<AppState>,
-                                Query(type_param) : Query<GameTypeQuery>,
-                                body: String) -> Result<String, AnyhowError> {
+                                type_param: Result<Query<GameTypeQuery>, QueryRejection>,
+                                body: String) -> Result<String, ApiError> {
 
-    let game = game::create_game(&type_param.kind, &body)?;
-    let mut storage = state.game_store.lock().unwrap();
-    Ok(storage.insert_game(game))
+    let Query(type_param) = type_param
+        .map_err(|rejection| ApiError::new(StatusCode::BAD_REQUEST, "malformed_query", rejection.body_text()))?;
+
+    // client seeds are submitted afterwards, once the commitment can be read from the fairness endpoint
+    let storage = state.game_store.as_ref();
+    let id = state.admit(|| storage.create_game(type_param.kind, &body, Fairness::new()))?;
+
+    state.escrow(&id)?;
+    Ok(id)
 }
 
 pub async fn transition_game(Path(id): Path<String>,
                                     State(state): State<AppState>,
-                                    body: String)  -> Result<(), AnyhowError>{
-    let mut storage = state.game_store.lock().unwrap();
-    let game = storage.get_game(&id).ok_or(NotFound(id))?;
-    match game.get_type() {
-        Fts => Ok(game.transition(serde_json::from_str(&body)?)?),
-        Cta => Ok(game.transition(serde_json::from_str(&body)?)?)
+                                    headers: HeaderMap,
+                                    body: String)  -> Result<(), ApiError>{
+    let transition: Transition = serde_json::from_str(&body)?;
+
+    if let Transition::Game(GameTransition::End { .. }) = transition {
+        operator_of(&state, &headers, "void games")?;
     }
+
+    state.game_store.transition_game(&id, transition)?;
+    Ok(state.settle(&id)?)
+}
+
+// Players are not authenticated, their wagers are placed, amended and cancelled by operators on their behalf
+pub async fn place_wager(Path(id): Path<String>,
+                         State(state): State<AppState>,
+                         headers: HeaderMap,
+                         body: String) -> Result<(), ApiError> {
+    operator_of(&state, &headers, "change wagers")?;
+    let place_dto: PlaceWagerDto = serde_json::from_str(&body)?;
+    Ok(state.change_wagers(&id, WagerChange::Place { player_id: place_dto.player_id, wager: place_dto.wager })?)
+}
+
+pub async fn amend_wager(Path((id, wager_id)): Path<(String, u32)>,
+                         State(state): State<AppState>,
+                         headers: HeaderMap,
+                         body: String) -> Result<(), ApiError> {
+    operator_of(&state, &headers, "change wagers")?;
+    let amend_dto: AmendWagerDto = serde_json::from_str(&body)?;
+    Ok(state.change_wagers(&id, WagerChange::Amend { wager_id, amount: amend_dto.amount })?)
+}
+
+pub async fn cancel_wager(Path((id, wager_id)): Path<(String, u32)>,
+                          State(state): State<AppState>,
+                          headers: HeaderMap) -> Result<(), ApiError> {
+    operator_of(&state, &headers, "change wagers")?;
+    Ok(state.change_wagers(&id, WagerChange::Cancel { wager_id })?)
 }
 
 pub async fn get_transitions(Path(id): Path<String>,
-                             State(state): State<AppState>) -> Result<Json<Vec<Transition>>, AnyhowError> {
-    let mut storage = state.game_store.lock().unwrap();
-    let game = storage.get_game(&id).ok_or(NotFound(id))?;
+                             State(state): State<AppState>) -> Result<Json<Vec<Transition>>, ApiError> {
+    let game_ref = state.game_store.get_game(&id).ok_or(NotFound(id))?;
+    let stored = game_ref.lock().unwrap();
+    let game = stored.get_game();
     Ok(Json(game.get_valid_transitions()))
 }
 
+pub async fn get_state(Path(id): Path<String>,
+                       State(state): State<AppState>) -> Result<String, ApiError> {
+    let game_ref = state.game_store.get_game(&id).ok_or(NotFound(id))?;
+    let stored = game_ref.lock().unwrap();
+    let game = stored.get_game();
+
+    let state_dto = GameStateDto {
+        state: game.get_state(),
+        deck_pool: game.get_deck_pool()
+            .iter()
+            .map(|deck| DeckLayoutDto { cards: deck.len(), dealt: deck.get_dealt_cards().len() })
+            .collect()
+    };
+
+    Ok(serde_json::to_string(&state_dto)?)
+}
+
+pub async fn get_game(Path(id): Path<String>,
+                      State(state): State<AppState>) -> Result<String, ApiError> {
+    let game_ref = state.game_store.get_game(&id).ok_or(NotFound(id))?;
+    let stored = game_ref.lock().unwrap();
+    let game = stored.get_game();
+    Ok(serde_json::to_string(&game.get_details())?)
+}
+
+pub async fn get_wager_odds(Path(id): Path<String>,
+                            State(state): State<AppState>) -> Result<String, ApiError> {
+    let game_ref = state.game_store.get_game(&id).ok_or(NotFound(id))?;
+    let stored = game_ref.lock().unwrap();
+    let game = stored.get_game();
+    Ok(serde_json::to_string(&game.get_wager_odds())?)
+}
+
+pub async fn add_client_seeds(Path(id): Path<String>,
+                              State(state): State<AppState>,
+                              body: String) -> Result<(), ApiError> {
+    let client_seeds_dto: ClientSeedsDto = serde_json::from_str(&body)?;
+
+    let mut violations = Violations::new();
+    if client_seeds_dto.client_seeds.is_empty() {
+        violations.add(pointer(&["client_seeds"]), "At least one client seed is required");
+    }
+    violations.into_result()?;
+
+    Ok(state.game_store.add_client_seeds(&id, client_seeds_dto.client_seeds)?)
+}
+
+pub async fn get_fairness(Path(id): Path<String>,
+                          State(state): State<AppState>) -> Result<String, ApiError> {
+    let game_ref = state.game_store.get_game(&id).ok_or(NotFound(id))?;
+    let stored = game_ref.lock().unwrap();
+    let (game, fairness) = (stored.get_game(), stored.get_fairness());
+
+    let revealed = game.get_state().is_over();
+
+    let fairness_dto = FairnessDto {
+        commitment: hex::encode(fairness.get_commitment()),
+        client_seeds: fairness.get_client_seeds(),
+        server_seed: revealed.then(|| hex::encode(fairness.get_server_seed())),
+        shuffle_seed: revealed.then(|| hex::encode(game.get_seed()))
+    };
+
+    Ok(serde_json::to_string(&fairness_dto)?)
+}
+
+// Replays the shuffle of a finished game from its revealed seeds
+pub async fn verify_fairness(body: String) -> Result<Json<VerificationDto>, ApiError> {
+    let verify_dto: VerifyDto = serde_json::from_str(&body)?;
+    let fairness = Fairness::from_revealed(&verify_dto.server_seed, verify_dto.client_seeds).map_err(validation)?;
+
+    let mut deck = Deck::default();
+    deck.shuffle(&mut fairness.get_shuffler());
+
+    let flop_count = verify_dto.flops.map_or(deck.len() / 3, usize::from);
+    let dealt = &deck.get_cards()[..cmp::min(flop_count * 3, deck.len())];
+
+    let verification_dto = VerificationDto {
+        commitment: hex::encode(fairness.get_commitment()),
+        shuffle_seed: hex::encode(fairness.get_shuffle_seed()),
+        deck: deck.get_cards().iter().map(Card::to_string).collect(),
+        flopped_at: find_first_flop(dealt)
+    };
+
+    Ok(Json(verification_dto))
+}
+
+pub async fn price_fts_odds(body: String) -> Result<Json<PricingDto>, ApiError> {
+    let pricing_dto: FtsPricingDto = serde_json::from_str(&body)?;
+    pricing_dto.wager_type.validate(pointer(&["wager_type"])).into_result()?;
+
+    let deck = Deck::default();
+    let flop_count = (deck.len() / 3) as u8;
+
+    let pricing = probability::price(
+        &[(&pricing_dto.wager_type, 1.0)],
+        pricing_dto.house_edge,
+        &probability::flop_distribution(&deck, flop_count),
+        flop_count
+    ).map_err(validation)?;
+
+    Ok(Json(PricingDto { odds: pricing.odds, house_edge: pricing.house_edge }))
+}
+
 pub async fn get_payout(Path(id): Path<String>,
-                               State(state): State<AppState>) -> Result<String, AnyhowError> {
-    let mut storage = state.game_store.lock().unwrap();
-    let game = storage.get_game(&id).ok_or(NotFound(id))?;
+                               State(state): State<AppState>) -> Result<String, ApiError> {
+    let game_ref = state.game_store.get_game(&id).ok_or(NotFound(id))?;
+    let stored = game_ref.lock().unwrap();
+    let game = stored.get_game();
     match serde_json::to_string(&game.get_payout()?) {
         Ok(payout_json) => Ok(payout_json),
-        Err(e) => Err(AnyhowError::from(e))
+        Err(e) => Err(ApiError::from(e))
+    }
+}
+
+pub async fn get_balance(Path((kind, id)): Path<(AccountKind, String)>,
+                         State(state): State<AppState>) -> Result<String, ApiError> {
+    let account = account_of(kind, id);
+    let balances = ledger_of(&state)?.get_balances(&account);
+    Ok(serde_json::to_string(&BalanceDto { account: &account, balances })?)
+}
+
+pub async fn get_statement(Path((kind, id)): Path<(AccountKind, String)>,
+                           State(state): State<AppState>) -> Result<String, ApiError> {
+    let account = account_of(kind, id);
+    let lines = ledger_of(&state)?.get_statement(&account);
+    Ok(serde_json::to_string(&StatementDto { account: &account, lines })?)
+}
+
+// Money only enters or leaves wallets through operators
+pub async fn deposit(Path((kind, id)): Path<(AccountKind, String)>,
+                     State(state): State<AppState>,
+                     headers: HeaderMap,
+                     body: String) -> Result<Json<EntryDto>, ApiError> {
+    let ledger = ledger_of(&state)?;
+    operator_of(&state, &headers, "deposit")?;
+    let transfer_dto = transfer_of(&body)?;
+    let entry_id = ledger.deposit(account_of(kind, id), transfer_dto.currency, transfer_dto.amount)?;
+    Ok(Json(EntryDto { entry_id }))
+}
+
+pub async fn withdraw(Path((kind, id)): Path<(AccountKind, String)>,
+                      State(state): State<AppState>,
+                      headers: HeaderMap,
+                      body: String) -> Result<Json<EntryDto>, ApiError> {
+    let ledger = ledger_of(&state)?;
+    operator_of(&state, &headers, "withdraw")?;
+    let transfer_dto = transfer_of(&body)?;
+    let entry_id = ledger.withdraw(account_of(kind, id), transfer_dto.currency, transfer_dto.amount)?;
+    Ok(Json(EntryDto { entry_id }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::expiry::{Evictor, ExpiryPolicy};
+    use crate::money::{Currency, Money};
+    use super::*;
+
+    const FTS_CONFIG: &str = r#"{ "wagers": { "player1": [{ "id": 0, "wager_type": "FullDeck", "amount": 10 }] }, "house_id": "house" }"#;
+
+    fn query(kind: game::GameType) -> Result<Query<GameTypeQuery>, QueryRejection> {
+        Ok(Query(GameTypeQuery { kind }))
+    }
+
+    fn operator(key: &str) -> HeaderMap {
+        HeaderMap::from_iter([(OPERATOR_KEY_HEADER.parse().unwrap(), key.parse().unwrap())])
+    }
+
+    fn ok<T>(result: Result<T, ApiError>) -> Result<T, Error> {
+        result.map_err(|api_error| anyhow::anyhow!(api_error.error_dto.message))
+    }
+
+    fn status_and_code<T>(result: Result<T, ApiError>) -> (StatusCode, &'static str) {
+        match result {
+            Ok(_) => (StatusCode::OK, ""),
+            Err(api_error) => (api_error.status, api_error.error_dto.code)
+        }
+    }
+
+    #[tokio::test]
+    async fn error_statuses() -> Result<(), Error> {
+        let state = AppState::new();
+
+        assert_eq!(
+            status_and_code(get_state(Path("missing".to_string()), State(state.clone())).await),
+            (StatusCode::NOT_FOUND, "not_found")
+        );
+
+        assert_eq!(
+            status_and_code(create_game(State(state.clone()), query(game::GameType::Fts), "{ \"wagers\": ".to_string()).await),
+            (StatusCode::BAD_REQUEST, "malformed_body")
+        );
+
+        assert_eq!(
+            status_and_code(create_game(State(state.clone()), query(game::GameType::Fts), r#"{ "wagers": {} }"#.to_string()).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+        );
+
+        let zero_cuts = FTS_CONFIG.replace("FullDeck", "Forward").replace("\"house\"", "\"house\", \"cuts\": 0");
+        assert_eq!(
+            status_and_code(create_game(State(state.clone()), query(game::GameType::Cta), zero_cuts).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+        );
+
+        let id = ok(create_game(State(state.clone()), query(game::GameType::Fts), FTS_CONFIG.to_string()).await)?;
+        let start = r#"{ "Game": "Start" }"#.to_string();
+
+        assert_eq!(status_and_code(transition_game(Path(id.clone()), State(state.clone()), HeaderMap::new(), start.clone()).await), (StatusCode::OK, ""));
+        assert_eq!(
+            status_and_code(transition_game(Path(id), State(state), HeaderMap::new(), start).await),
+            (StatusCode::CONFLICT, "invalid_transition")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn error_body() -> Result<(), Error> {
+        let api_error = ApiError::from(serde_json::from_str::<VerifyDto>("{ \"server_seed\": 1 }").err().unwrap());
+        let body = serde_json::to_value(&api_error.error_dto)?;
+
+        assert_eq!(body["code"], "malformed_body");
+        assert_eq!(body["details"], json!({ "line": 1, "column": 18 }));
+        assert!(body["message"].as_str().is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn validation_details() -> Result<(), Error> {
+        let payload = r#"{ "wagers": { "player1": [{ "id": 0, "wager_type": "Sideways", "amount": 10 }, { "id": 1, "wager_type": "FullDeck", "amount": -5 }] } }"#;
+        let api_error = create_game(State(AppState::new()), query(game::GameType::Fts), payload.to_string()).await.err().unwrap();
+        let body = serde_json::to_value(&api_error.error_dto)?;
+
+        assert_eq!(api_error.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let paths: Vec<&str> = body["details"].as_array().unwrap().iter()
+            .map(|violation| violation["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(paths, vec!["/house_id", "/wagers/player1/0/wager_type"]);
+        Ok(())
+    }
+
+    fn balance_of(state: &AppState, account: Account) -> i64 {
+        let balances = state.ledger.as_ref().unwrap().get_balances(&account);
+        balances.get(&Currency::default()).map_or(0, |balance| balance.get_minor())
+    }
+
+    #[tokio::test]
+    async fn settlement() -> Result<(), Error> {
+        let disabled = get_balance(Path((AccountKind::Players, "player1".to_string())), State(AppState::new())).await;
+        assert_eq!(status_and_code(disabled), (StatusCode::NOT_FOUND, "ledger_disabled"));
+
+        let state = AppState::new().with_ledger(Ledger::new())?;
+        let player = || Account::Player("player1".to_string());
+        let house = || Account::House("house".to_string());
+
+        // a full deck wager of 10 loses at most 17 times its amount, and wins as much when the first flop is a flop
+        state.ledger.as_ref().unwrap().deposit(player(), Currency::default(), Money::from_minor(170))?;
+        state.ledger.as_ref().unwrap().deposit(house(), Currency::default(), Money::from_minor(170))?;
+
+        let id = ok(create_game(State(state.clone()), query(game::GameType::Fts), FTS_CONFIG.to_string()).await)?;
+        assert_eq!(balance_of(&state, player()), 0);
+        assert_eq!(balance_of(&state, house()), 0);
+        assert_eq!(balance_of(&state, Account::Escrow(id.clone())), 340);
+
+        // Fts games end as soon as they start
+        ok(transition_game(Path(id.clone()), State(state.clone()), HeaderMap::new(), r#"{ "Game": "Start" }"#.to_string()).await)?;
+        state.settle(&id)?;
+
+        let statement = ok(get_statement(Path((AccountKind::Players, "player1".to_string())), State(state.clone())).await)?;
+        let statement: serde_json::Value = serde_json::from_str(&statement)?;
+        let kinds: Vec<&str> = statement["lines"].as_array().unwrap().iter().map(|line| line["kind"].as_str().unwrap()).collect();
+        // a lost wager nets out against its escrow and leaves no settlement line
+        assert_eq!(kinds[..2], ["Deposit", "Escrow"]);
+
+        let balance = ok(get_balance(Path((AccountKind::Houses, "house".to_string())), State(state.clone())).await)?;
+        let balance: serde_json::Value = serde_json::from_str(&balance)?;
+        assert_eq!(balance["balances"]["XXX"].as_i64().unwrap() + balance_of(&state, player()), 340);
+        assert_eq!(balance_of(&state, Account::Escrow(id)), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn void() -> Result<(), Error> {
+        let state = AppState::new().with_ledger(Ledger::new())?.with_operator_key("secret".to_string());
+        let player = || Account::Player("player1".to_string());
+        let house = || Account::House("house".to_string());
+        state.ledger.as_ref().unwrap().deposit(player(), Currency::default(), Money::from_minor(170))?;
+        state.ledger.as_ref().unwrap().deposit(house(), Currency::default(), Money::from_minor(170))?;
+
+        let id = ok(create_game(State(state.clone()), query(game::GameType::Fts), FTS_CONFIG.to_string()).await)?;
+        let end = r#"{ "Game": { "End": { "reason": "misdeal" } } }"#.to_string();
+
+        assert_eq!(
+            status_and_code(transition_game(Path(id.clone()), State(state.clone()), HeaderMap::new(), end.clone()).await),
+            (StatusCode::FORBIDDEN, "operator_only")
+        );
+        assert_eq!(
+            status_and_code(transition_game(Path(id.clone()), State(state.clone()), operator("guess"), end.clone()).await),
+            (StatusCode::FORBIDDEN, "operator_only")
+        );
+        assert_eq!(
+            status_and_code(transition_game(Path(id.clone()), State(state.clone()), operator("secret"), end.replace("misdeal", " ")).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+        );
+        assert_eq!(
+            status_and_code(transition_game(Path(id.clone()), State(state.clone()), operator("secret"), end.clone()).await),
+            (StatusCode::OK, "")
+        );
+
+        // the escrow goes back where it came from
+        assert!(state.ledger.as_ref().unwrap().is_settled(&id));
+        assert_eq!(balance_of(&state, player()), 170);
+        assert_eq!(balance_of(&state, house()), 170);
+        assert_eq!(balance_of(&state, Account::Escrow(id.clone())), 0);
+
+        assert_eq!(
+            status_and_code(transition_game(Path(id), State(state), operator("secret"), end).await),
+            (StatusCode::CONFLICT, "invalid_transition")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn betting_window() -> Result<(), Error> {
+        let state = AppState::new().with_ledger(Ledger::new())?.with_operator_key("secret".to_string());
+        let player2 = || Account::Player("player2".to_string());
+        state.ledger.as_ref().unwrap().deposit(Account::Player("player1".to_string()), Currency::default(), Money::from_minor(170))?;
+        state.ledger.as_ref().unwrap().deposit(Account::House("house".to_string()), Currency::default(), Money::from_minor(10000))?;
+
+        let id = ok(create_game(State(state.clone()), query(game::GameType::Fts), FTS_CONFIG.to_string()).await)?;
+        let place = r#"{ "player_id": "player2", "wager": { "id": 1, "wager_type": { "AtFlop": 0 }, "amount": 10 } }"#;
+        let wager = |wager_id: u32| Path((id.clone(), wager_id));
+
+        // players cannot change wagers themselves, not even their own
+        assert_eq!(
+            status_and_code(place_wager(Path(id.clone()), State(state.clone()), HeaderMap::new(), place.to_string()).await),
+            (StatusCode::FORBIDDEN, "operator_only")
+        );
+        assert_eq!(status_and_code(cancel_wager(wager(0), State(state.clone()), operator("guess")).await), (StatusCode::FORBIDDEN, "operator_only"));
+
+        // the change is refused as a whole when the escrow cannot follow
+        assert_eq!(
+            status_and_code(place_wager(Path(id.clone()), State(state.clone()), operator("secret"), place.to_string()).await),
+            (StatusCode::CONFLICT, "insufficient_funds")
+        );
+        assert_eq!(status_and_code(cancel_wager(wager(1), State(state.clone()), operator("secret")).await), (StatusCode::NOT_FOUND, "wager_not_found"));
+
+        state.ledger.as_ref().unwrap().deposit(player2(), Currency::default(), Money::from_minor(10))?;
+        assert_eq!(status_and_code(place_wager(Path(id.clone()), State(state.clone()), operator("secret"), place.to_string()).await), (StatusCode::OK, ""));
+        assert_eq!(balance_of(&state, player2()), 0);
+
+        assert_eq!(
+            status_and_code(amend_wager(wager(1), State(state.clone()), operator("secret"), r#"{ "amount": 4 }"#.to_string()).await),
+            (StatusCode::OK, "")
+        );
+        assert_eq!(balance_of(&state, player2()), 6);
+        assert_eq!(
+            status_and_code(amend_wager(wager(1), State(state.clone()), operator("secret"), r#"{ "amount": 0 }"#.to_string()).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+        );
+
+        assert_eq!(status_and_code(cancel_wager(wager(1), State(state.clone()), operator("secret")).await), (StatusCode::OK, ""));
+        assert_eq!(balance_of(&state, player2()), 10);
+
+        ok(transition_game(Path(id.clone()), State(state.clone()), HeaderMap::new(), r#"{ "Game": "Start" }"#.to_string()).await)?;
+        assert_eq!(status_and_code(cancel_wager(wager(0), State(state.clone()), operator("secret")).await), (StatusCode::CONFLICT, "betting_closed"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn odds_pricing_validation() {
+        let price = |wager_type: &str| price_fts_odds(format!(r#"{{ "wager_type": {}, "house_edge": 0.05 }}"#, wager_type));
+
+        assert_eq!(status_and_code(price(r#"{ "FlopRange": [2, 5] }"#).await), (StatusCode::OK, ""));
+
+        for wager_type in [r#"{ "FlopRange": [5, 2] }"#, r#"{ "FlopRange": [0, 255] }"#, r#"{ "AtFlop": 17 }"#] {
+            let api_error = price(wager_type).await.err().unwrap();
+            assert_eq!(api_error.status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(serde_json::to_value(&api_error.error_dto).unwrap()["details"][0]["path"], "/wager_type");
+        }
+    }
+
+    #[tokio::test]
+    async fn client_seeds_follow_commitment() -> Result<(), Error> {
+        let state = AppState::new();
+        let fairness_of = |id: String| get_fairness(Path(id), State(state.clone()));
+        let seed_of = |id: &str| {
+            let game_ref = state.game_store.get_game(id).unwrap();
+            let seed = *game_ref.lock().unwrap().get_game().get_seed();
+            seed
+        };
+
+        let with_seeds = FTS_CONFIG.replace("\"house\"", "\"house\", \"client_seeds\": { \"player1\": \"lucky\" }");
+        assert_eq!(
+            status_and_code(create_game(State(state.clone()), query(game::GameType::Fts), with_seeds).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+        );
+
+        let id = ok(create_game(State(state.clone()), query(game::GameType::Fts), FTS_CONFIG.to_string()).await)?;
+        let committed: serde_json::Value = serde_json::from_str(&ok(fairness_of(id.clone()).await)?)?;
+        let seed_before = seed_of(&id);
+
+        let seeds = r#"{ "client_seeds": { "player1": "lucky" } }"#.to_string();
+        assert_eq!(
+            status_and_code(add_client_seeds(Path(id.clone()), State(state.clone()), r#"{ "client_seeds": {} }"#.to_string()).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+        );
+        assert_eq!(status_and_code(add_client_seeds(Path(id.clone()), State(state.clone()), seeds.clone()).await), (StatusCode::OK, ""));
+
+        // the commitment stays, the shuffle now depends on the client seed
+        let seeded: serde_json::Value = serde_json::from_str(&ok(fairness_of(id.clone()).await)?)?;
+        assert_eq!(seeded["commitment"], committed["commitment"]);
+        assert_eq!(seeded["client_seeds"], json!({ "player1": "lucky" }));
+        assert_ne!(seed_of(&id), seed_before);
+
+        ok(transition_game(Path(id.clone()), State(state.clone()), HeaderMap::new(), r#"{ "Game": "Start" }"#.to_string()).await)?;
+        assert_eq!(status_and_code(add_client_seeds(Path(id), State(state), seeds).await), (StatusCode::CONFLICT, "seeding_closed"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn escrow_needs_funds() -> Result<(), Error> {
+        let state = AppState::new().with_ledger(Ledger::new())?;
+        let player = || Account::Player("player1".to_string());
+        state.ledger.as_ref().unwrap().deposit(player(), Currency::default(), Money::from_minor(169))?;
+        state.ledger.as_ref().unwrap().deposit(Account::House("house".to_string()), Currency::default(), Money::from_minor(170))?;
+
+        assert_eq!(
+            status_and_code(create_game(State(state.clone()), query(game::GameType::Fts), FTS_CONFIG.to_string()).await),
+            (StatusCode::CONFLICT, "insufficient_funds")
+        );
+        assert_eq!(state.game_store.game_count(), 0);
+
+        // games evicted before they start give their escrow back
+        let state = state.with_evictor(Evictor::new(ExpiryPolicy { setup_ttl: Duration::ZERO, ..ExpiryPolicy::default() }));
+        state.ledger.as_ref().unwrap().deposit(player(), Currency::default(), Money::from_minor(1))?;
+        ok(create_game(State(state.clone()), query(game::GameType::Fts), FTS_CONFIG.to_string()).await)?;
+        assert_eq!(state.evict_expired()?, 1);
+
+        assert_eq!(balance_of(&state, player()), 170);
+        assert_eq!(balance_of(&state, Account::House("house".to_string())), 170);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn evicted_games_settle_first() -> Result<(), Error> {
+        let policy = ExpiryPolicy { ended_ttl: Duration::ZERO, max_games: 1, ..ExpiryPolicy::default() };
+        let state = AppState::new().with_ledger(Ledger::new())?.with_evictor(Evictor::new(policy));
+        let ledger = state.ledger.clone().unwrap();
+        ledger.deposit(Account::Player("player1".to_string()), Currency::default(), Money::from_minor(340))?;
+        ledger.deposit(Account::House("house".to_string()), Currency::default(), Money::from_minor(340))?;
+
+        let create = || create_game(State(state.clone()), query(game::GameType::Fts), FTS_CONFIG.to_string());
+        let end_unsettled = |id: &str| state.game_store.transition_game(id, Transition::Game(GameTransition::Start));
+
+        // the ended game makes room for the next one, it settles on its way out rather than giving its escrow back
+        let first_id = ok(create().await)?;
+        end_unsettled(&first_id)?;
+        let second_id = ok(create().await)?;
+        assert!(state.game_store.get_game(&first_id).is_none());
+        assert!(ledger.is_settled(&first_id));
+
+        end_unsettled(&second_id)?;
+        assert_eq!(state.evict_expired()?, 1);
+        assert!(ledger.is_settled(&second_id));
+        assert!(ledger.get_escrowed_games().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn house_limits() -> Result<(), Error> {
+        let create = |state: &AppState| create_game(State(state.clone()), query(game::GameType::Fts), FTS_CONFIG.to_string());
+
+        // the house has to cover the best result of the player
+        let state = AppState::new().with_ledger(Ledger::new())?;
+        state.ledger.as_ref().unwrap().deposit(Account::Player("player1".to_string()), Currency::default(), Money::from_minor(340))?;
+        state.ledger.as_ref().unwrap().deposit(Account::House("house".to_string()), Currency::default(), Money::from_minor(300))?;
+
+        assert_eq!(status_and_code(create(&state).await), (StatusCode::OK, ""));
+        assert_eq!(status_and_code(create(&state).await), (StatusCode::CONFLICT, "insufficient_funds"));
+
+        // a single game fits within the exposure limit, a second one would not
+        let state = AppState::new().with_ledger(Ledger::new().with_exposure_limits("XXX:200".parse()?))?;
+        state.ledger.as_ref().unwrap().deposit(Account::Player("player1".to_string()), Currency::default(), Money::from_minor(340))?;
+        state.ledger.as_ref().unwrap().deposit(Account::House("house".to_string()), Currency::default(), Money::from_minor(1000))?;
+
+        assert_eq!(status_and_code(create(&state).await), (StatusCode::OK, ""));
+        assert_eq!(status_and_code(create(&state).await), (StatusCode::CONFLICT, "exposure_exceeded"));
+        assert_eq!(state.game_store.game_count(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn transfers() {
+        let state = AppState::new().with_ledger(Ledger::new()).unwrap().with_operator_key("secret".to_string());
+        let account = || Path((AccountKind::Players, "player1".to_string()));
+        let amount = |amount: i64| format!(r#"{{ "currency": "EUR", "amount": {} }}"#, amount);
+
+        // players cannot fund or drain wallets themselves
+        assert_eq!(
+            status_and_code(deposit(account(), State(state.clone()), HeaderMap::new(), amount(100)).await),
+            (StatusCode::FORBIDDEN, "operator_only")
+        );
+        assert_eq!(
+            status_and_code(withdraw(account(), State(state.clone()), operator("guess"), amount(100)).await),
+            (StatusCode::FORBIDDEN, "operator_only")
+        );
+
+        assert_eq!(
+            status_and_code(deposit(account(), State(state.clone()), operator("secret"), amount(0)).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+        );
+        assert_eq!(
+            status_and_code(deposit(account(), State(state.clone()), operator("secret"), amount(100)).await),
+            (StatusCode::OK, "")
+        );
+        assert_eq!(
+            status_and_code(withdraw(account(), State(state), operator("secret"), amount(150)).await),
+            (StatusCode::CONFLICT, "insufficient_funds")
+        );
     }
 }
\ No newline at end of file
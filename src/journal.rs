@@ -0,0 +1,64 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+
+// Records of a JSON lines journal with their line numbers, and the length of the file they take up. A crash while
+// appending leaves the last line cut short, that line is dropped with a warning and writers truncate the file to
+// the returned length. A line that cannot be read anywhere else means the journal is corrupt.
+pub(crate) fn read_records<T: DeserializeOwned>(path: &Path) -> Result<(Vec<(usize, T)>, u64)> {
+    if !path.exists() {
+        return Ok((Vec::new(), 0));
+    }
+
+    let contents = fs::read(path)?;
+    let mut records = Vec::new();
+    let mut complete_len = 0;
+
+    let lines: Vec<&[u8]> = contents.split_inclusive(|byte| *byte == b'\n').collect();
+    for (i, line) in lines.iter().enumerate() {
+        let last = i + 1 == lines.len();
+        let record = line.strip_suffix(b"\n")
+            .ok_or_else(|| anyhow!("missing end of line"))
+            .and_then(|line| if line.is_empty() { Ok(None) } else { Ok(Some(serde_json::from_slice(line)?)) });
+
+        match record {
+            Ok(record) => {
+                records.extend(record.map(|record| (i + 1, record)));
+                complete_len += line.len() as u64;
+            },
+            Err(e) if last => eprintln!("Dropping incomplete last line {} of {}: {}", i + 1, path.display(), e),
+            Err(e) => return Err(anyhow!("Could not read line {} of {}: {}", i + 1, path.display(), e))
+        }
+    }
+
+    Ok((records, complete_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use uuid::Uuid;
+    use super::*;
+
+    #[test]
+    fn torn_last_line() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("zsonkorp-journal-{}.jsonl", Uuid::new_v4()));
+
+        fs::write(&path, "{\"a\":1}\n\n{\"b\":2}\n{\"c\":")?;
+        let (records, complete_len) = read_records::<Value>(&path)?;
+        assert_eq!(records.iter().map(|(line, _)| *line).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(complete_len, 17);
+
+        // a record without its end of line was not completely written either
+        fs::write(&path, "{\"a\":1}\n{\"b\":2}")?;
+        assert_eq!(read_records::<Value>(&path)?.0.len(), 1);
+
+        fs::write(&path, "{\"a\":1}\n{\"b\":\n{\"c\":3}\n")?;
+        let result = read_records::<Value>(&path);
+        fs::remove_file(&path)?;
+
+        assert!(result.is_err());
+        Ok(())
+    }
+}
\ No newline at end of file
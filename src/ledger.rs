@@ -0,0 +1,560 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::journal;
+use crate::money::{Currency, Money};
+use crate::payout::Payout;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Insufficient {currency} funds in {account}: {} available, {} requested", .available.in_currency(.currency), .requested.in_currency(.currency))]
+    InsufficientFunds {
+        account: Account,
+        currency: Currency,
+        available: Money,
+        requested: Money
+    },
+
+    #[error("Escrowing {} {currency} would bring the exposure of {account} to {}, past its limit of {}",
+        .requested.in_currency(.currency), .exposure.in_currency(.currency), .limit.in_currency(.currency))]
+    ExposureExceeded {
+        account: Account,
+        currency: Currency,
+        requested: Money,
+        exposure: Money,
+        limit: Money
+    },
+
+    #[error("Postings do not balance in {0}")]
+    Unbalanced(Currency)
+}
+
+// Players and houses have separate wallets even when they share an id. Money enters and leaves the books
+// through the external account, whose balance is minus everything held in the wallets. Stakes of a running
+// game are held in its escrow account.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum Account {
+    Player(String),
+    House(String),
+    Escrow(String),
+    External
+}
+
+impl Account {
+    // Payouts without a wager are house lines
+    fn of(payout: &Payout) -> Self {
+        match payout.get_wager_id() {
+            Some(_) => Account::Player(payout.get_player_id().to_string()),
+            None => Account::House(payout.get_player_id().to_string())
+        }
+    }
+}
+
+impl Display for Account {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Account::Player(id) => write!(f, "player {}", id),
+            Account::House(id) => write!(f, "house {}", id),
+            Account::Escrow(game_id) => write!(f, "escrow of game {}", game_id),
+            Account::External => write!(f, "external account")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Posting {
+    account: Account,
+    currency: Currency,
+    amount: Money       // positive -> credited to the account, negative -> debited
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum EntryKind {
+    Escrow { game_id: String },
+    Settlement { game_id: String },
+    Release { game_id: String },     // escrow given back to the players of a game that will never settle
+    Deposit,
+    Withdrawal
+}
+
+// The postings of an entry add up to zero in every currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    id: u64,
+    #[serde(flatten)]
+    kind: EntryKind,
+    postings: Vec<Posting>,
+    recorded_at: u64        // seconds since the Unix epoch
+}
+
+// A posting of a statement with the balance of its currency once applied
+#[derive(Debug, Serialize)]
+pub struct StatementLine {
+    pub entry_id: u64,
+    #[serde(flatten)]
+    pub kind: EntryKind,
+    pub currency: Currency,
+    pub amount: Money,
+    pub balance: Money,
+    pub recorded_at: u64
+}
+
+// Balances an entry leaves the accounts it posts to with
+type Balances = Vec<((Account, Currency), Money)>;
+
+struct Books {
+    entries: Vec<Entry>,
+    balances: HashMap<(Account, Currency), Money>,
+    settled: HashSet<String>,
+    escrows: HashMap<String, Vec<Posting>>,     // postings reserving the stakes of games not settled yet
+    journal: Option<File>
+}
+
+impl Books {
+    fn get_balance(&self, account: &Account, currency: &Currency) -> Money {
+        self.balances.get(&(account.clone(), currency.clone())).copied().unwrap_or(Money::ZERO)
+    }
+
+    // What the account has in the escrow of games that have not settled yet
+    fn get_exposure(&self, account: &Account, currency: &Currency) -> Result<Money> {
+        self.escrows.values()
+            .flatten()
+            .filter(|posting| posting.account == *account && posting.currency == *currency)
+            .try_fold(Money::ZERO, |exposure, posting| exposure.checked_sub(posting.amount))
+    }
+
+    // Nothing changes unless every posting applies, the entry is journaled before it is applied
+    fn record(&mut self, kind: EntryKind, postings: Vec<Posting>) -> Result<u64> {
+        let entry = Entry {
+            id: self.entries.len() as u64,
+            kind,
+            postings,
+            recorded_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()
+        };
+
+        let balances = self.apply(&entry)?;
+
+        if let Some(journal) = &mut self.journal {
+            writeln!(journal, "{}", serde_json::to_string(&entry)?)?;
+            journal.sync_data()?;
+        }
+
+        Ok(self.commit(entry, balances))
+    }
+
+    fn apply(&self, entry: &Entry) -> Result<Balances> {
+        let mut totals: BTreeMap<&Currency, Money> = BTreeMap::new();
+        let mut balances: HashMap<(Account, Currency), Money> = HashMap::new();
+
+        for posting in &entry.postings {
+            let total = totals.entry(&posting.currency).or_insert(Money::ZERO);
+            *total = total.checked_add(posting.amount)?;
+
+            let key = (posting.account.clone(), posting.currency.clone());
+            let balance = balances.get(&key).copied().unwrap_or_else(|| self.get_balance(&key.0, &key.1));
+            balances.insert(key, balance.checked_add(posting.amount)?);
+        }
+
+        if let Some((currency, _)) = totals.into_iter().find(|(_, total)| !total.is_zero()) {
+            return Err(anyhow!(Error::Unbalanced(currency.clone())));
+        }
+
+        Ok(balances.into_iter().collect())
+    }
+
+    fn commit(&mut self, entry: Entry, balances: Balances) -> u64 {
+        let id = entry.id;
+
+        self.balances.extend(balances);
+
+        match &entry.kind {
+            EntryKind::Escrow { game_id } => {
+                self.escrows.entry(game_id.clone()).or_default().extend(entry.postings.iter().cloned());
+            },
+            EntryKind::Settlement { game_id } => {
+                self.escrows.remove(game_id);
+                self.settled.insert(game_id.clone());
+            },
+            EntryKind::Release { game_id } => {
+                self.escrows.remove(game_id);
+            },
+            EntryKind::Deposit | EntryKind::Withdrawal => ()
+        }
+
+        self.entries.push(entry);
+        id
+    }
+}
+
+// Largest amount of each currency a house may have in escrow over all its running games, for example
+// "EUR:1000000,USD:500000". Currencies without a limit are only bounded by the bankroll. A game that would go
+// past a limit is rejected, games are not capped to fit.
+#[derive(Default)]
+pub struct ExposureLimits(BTreeMap<Currency, Money>);
+
+impl FromStr for ExposureLimits {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut limits = BTreeMap::new();
+
+        for limit in s.split(',').filter(|limit| !limit.is_empty()) {
+            let (currency, amount) = limit.split_once(':')
+                .ok_or_else(|| anyhow!("Invalid exposure limit {}, expected CURRENCY:AMOUNT", limit))?;
+            limits.insert(currency.trim().parse()?, Money::from_minor(amount.trim().parse()?));
+        }
+
+        Ok(ExposureLimits(limits))
+    }
+}
+
+// Double-entry books of every wallet, optionally journaled to a JSON lines file replayed when opened
+pub struct Ledger {
+    books: Mutex<Books>,
+    exposure_limits: ExposureLimits
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Ledger::new()
+    }
+}
+
+impl Ledger {
+    // Balances are lost on restart
+    pub fn new() -> Self {
+        Ledger {
+            books: Mutex::new(Books { entries: Vec::new(), balances: HashMap::new(), settled: HashSet::new(), escrows: HashMap::new(), journal: None }),
+            exposure_limits: ExposureLimits::default()
+        }
+    }
+
+    pub fn with_exposure_limits(self, exposure_limits: ExposureLimits) -> Self {
+        Ledger { exposure_limits, ..self }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let ledger = Ledger::new();
+
+        {
+            let mut books = ledger.books.lock().unwrap();
+
+            let (entries, complete_len) = journal::read_records::<Entry>(path)?;
+            for (line, entry) in entries {
+                let balances = books.apply(&entry)
+                    .map_err(|e| anyhow!("Could not replay ledger line {}: {}", line, e))?;
+                books.commit(entry, balances);
+            }
+
+            // new entries go after the last complete line, not after a dropped one
+            let journal = OpenOptions::new().create(true).append(true).open(path)?;
+            journal.set_len(complete_len)?;
+            books.journal = Some(journal);
+        }
+
+        Ok(ledger)
+    }
+
+    // Moves the max losses of the wagers and of the house from their wallets to the escrow of the game, all of
+    // them or none. The house bankroll is its wallet, its exposure is also bounded by the exposure limits. Games
+    // escrowing again only move the difference with what they already hold.
+    pub fn escrow(&self, game_id: &str, max_losses: &[Payout]) -> Result<u64> {
+        let mut books = self.books.lock().unwrap();
+        let mut stakes: BTreeMap<(Account, Currency), Money> = BTreeMap::new();
+
+        for max_loss in max_losses {
+            let stake = stakes.entry((Account::of(max_loss), max_loss.get_currency().clone())).or_insert(Money::ZERO);
+            *stake = stake.checked_sub(max_loss.get_amount())?;
+        }
+
+        let escrowed = books.escrows.get(game_id).into_iter()
+            .flatten()
+            .filter(|posting| !matches!(posting.account, Account::Escrow(_)));
+
+        for posting in escrowed {
+            let stake = stakes.entry((posting.account.clone(), posting.currency.clone())).or_insert(Money::ZERO);
+            *stake = stake.checked_add(posting.amount)?;
+        }
+
+        let mut postings = Vec::new();
+
+        for ((account, currency), stake) in stakes {
+            if stake.is_zero() {
+                continue;
+            }
+
+            let available = books.get_balance(&account, &currency);
+            if available < stake {
+                return Err(anyhow!(Error::InsufficientFunds { account, currency, available, requested: stake }));
+            }
+
+            if let (Account::House(_), Some(limit), true) = (&account, self.exposure_limits.0.get(&currency), stake.is_positive()) {
+                let exposure = books.get_exposure(&account, &currency)?.checked_add(stake)?;
+
+                if exposure > *limit {
+                    return Err(anyhow!(Error::ExposureExceeded { account, currency, requested: stake, exposure, limit: *limit }));
+                }
+            }
+
+            postings.push(Posting { account: Account::Escrow(game_id.to_string()), currency: currency.clone(), amount: stake });
+            postings.push(Posting { account, currency, amount: Money::ZERO.checked_sub(stake)? });
+        }
+
+        books.record(EntryKind::Escrow { game_id: game_id.to_string() }, postings)
+    }
+
+    // Gives the escrow of a game that will never settle back to its players
+    pub fn release(&self, game_id: &str) -> Result<bool> {
+        let mut books = self.books.lock().unwrap();
+
+        let Some(escrow) = books.escrows.get(game_id) else {
+            return Ok(false);
+        };
+
+        let postings = escrow.iter()
+            .map(|posting| Ok(Posting { amount: Money::ZERO.checked_sub(posting.amount)?, ..posting.clone() }))
+            .collect::<Result<Vec<Posting>>>()?;
+
+        books.record(EntryKind::Release { game_id: game_id.to_string() }, postings)?;
+        Ok(true)
+    }
+
+    // Games holding an escrow, they are either settled or released eventually
+    pub fn get_escrowed_games(&self) -> Vec<String> {
+        self.books.lock().unwrap().escrows.keys().cloned().collect()
+    }
+
+    // Posts the payouts of an ended game and gives its escrow back in the same entry, which turns the losses
+    // into payments. Returns false when the game was already settled.
+    pub fn settle(&self, game_id: &str, payouts: &[Payout]) -> Result<bool> {
+        let mut books = self.books.lock().unwrap();
+
+        if books.settled.contains(game_id) {
+            return Ok(false);
+        }
+
+        let mut amounts: BTreeMap<(Account, Currency), Money> = BTreeMap::new();
+
+        for posting in books.escrows.get(game_id).into_iter().flatten() {
+            let amount = amounts.entry((posting.account.clone(), posting.currency.clone())).or_insert(Money::ZERO);
+            *amount = amount.checked_sub(posting.amount)?;
+        }
+
+        for payout in payouts {
+            let amount = amounts.entry((Account::of(payout), payout.get_currency().clone())).or_insert(Money::ZERO);
+            *amount = amount.checked_add(payout.get_amount())?;
+        }
+
+        let postings = amounts.into_iter()
+            .filter(|(_, amount)| !amount.is_zero())
+            .map(|((account, currency), amount)| Posting { account, currency, amount })
+            .collect();
+
+        books.record(EntryKind::Settlement { game_id: game_id.to_string() }, postings)?;
+        Ok(true)
+    }
+
+    pub fn is_settled(&self, game_id: &str) -> bool {
+        self.books.lock().unwrap().settled.contains(game_id)
+    }
+
+    pub fn deposit(&self, account: Account, currency: Currency, amount: Money) -> Result<u64> {
+        let postings = vec![
+            Posting { account, currency: currency.clone(), amount },
+            Posting { account: Account::External, currency, amount: Money::ZERO.checked_sub(amount)? }
+        ];
+
+        self.books.lock().unwrap().record(EntryKind::Deposit, postings)
+    }
+
+    // Wallets cannot be withdrawn below zero
+    pub fn withdraw(&self, account: Account, currency: Currency, amount: Money) -> Result<u64> {
+        let mut books = self.books.lock().unwrap();
+
+        let available = books.get_balance(&account, &currency);
+        if available < amount {
+            return Err(anyhow!(Error::InsufficientFunds { account, currency, available, requested: amount }));
+        }
+
+        let postings = vec![
+            Posting { account, currency: currency.clone(), amount: Money::ZERO.checked_sub(amount)? },
+            Posting { account: Account::External, currency, amount }
+        ];
+
+        books.record(EntryKind::Withdrawal, postings)
+    }
+
+    pub fn get_balances(&self, account: &Account) -> BTreeMap<Currency, Money> {
+        self.books.lock().unwrap().balances.iter()
+            .filter(|((owner, _), _)| owner == account)
+            .map(|((_, currency), balance)| (currency.clone(), *balance))
+            .collect()
+    }
+
+    // Every posting to the account, oldest first
+    pub fn get_statement(&self, account: &Account) -> Vec<StatementLine> {
+        let books = self.books.lock().unwrap();
+        let mut balances: BTreeMap<&Currency, Money> = BTreeMap::new();
+        let mut lines = Vec::new();
+
+        for entry in &books.entries {
+            for posting in entry.postings.iter().filter(|posting| posting.account == *account) {
+                let balance = balances.entry(&posting.currency).or_insert(Money::ZERO);
+
+                // recorded balances never overflow, so neither do their running totals
+                *balance = balance.checked_add(posting.amount).unwrap_or(*balance);
+
+                lines.push(StatementLine {
+                    entry_id: entry.id,
+                    kind: entry.kind.clone(),
+                    currency: posting.currency.clone(),
+                    amount: posting.amount,
+                    balance: *balance,
+                    recorded_at: entry.recorded_at
+                });
+            }
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use uuid::Uuid;
+    use crate::config::{Cta, CtaWagerType, CurrencyRules};
+    use crate::player::Player;
+    use crate::wager::Wager;
+    use super::*;
+
+    fn eur() -> Currency {
+        "EUR".parse().unwrap()
+    }
+
+    fn player(id: &str) -> Account {
+        Account::Player(id.to_string())
+    }
+
+    #[test]
+    fn settles_once() -> Result<()> {
+        let config = Cta::new(
+            HashMap::from([(Player::new("player1".to_string()), vec![Wager::new(0, CtaWagerType::Forward, Money::from_minor(100))?])]),
+            "house".to_string(),
+            CurrencyRules::default(),
+            None
+        )?;
+        let wager = &config.get_base_config().get_wagers()[&Player::new("player1".to_string())][0];
+        let currency = Currency::default();
+
+        let payouts = vec![
+            Payout::new("player1", Some(wager), &currency, Money::from_minor(100))?,
+            Payout::new::<CtaWagerType>("house", None, &currency, Money::from_minor(-100))?
+        ];
+
+        let ledger = Ledger::new();
+        assert!(ledger.settle("game", &payouts)?);
+        assert!(!ledger.settle("game", &payouts)?);
+
+        assert_eq!(ledger.get_balances(&player("player1")), BTreeMap::from([(currency.clone(), Money::from_minor(100))]));
+        assert_eq!(ledger.get_balances(&Account::House("house".to_string())), BTreeMap::from([(currency, Money::from_minor(-100))]));
+        assert_eq!(ledger.get_statement(&player("player1")).len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn escrow_follows_the_max_losses() -> Result<()> {
+        let config = Cta::new(
+            HashMap::from([(Player::new("player1".to_string()), vec![Wager::new(0, CtaWagerType::Forward, Money::from_minor(100))?])]),
+            "house".to_string(),
+            CurrencyRules::default(),
+            None
+        )?;
+        let wager = &config.get_base_config().get_wagers()[&Player::new("player1".to_string())][0];
+        let currency = Currency::default();
+        let max_loss = |amount: i64| Payout::new("player1", Some(wager), &currency, Money::from_minor(amount));
+        let balances = |amount: i64| BTreeMap::from([(currency.clone(), Money::from_minor(amount))]);
+
+        let ledger = Ledger::new();
+        ledger.deposit(player("player1"), currency.clone(), Money::from_minor(150))?;
+
+        ledger.escrow("game", &[max_loss(-100)?])?;
+        ledger.escrow("game", &[max_loss(-150)?])?;
+        assert_eq!(ledger.get_balances(&player("player1")), balances(0));
+
+        // only the difference has to be covered
+        let error = ledger.escrow("game", &[max_loss(-200)?]).err().unwrap();
+        assert!(matches!(error.downcast_ref::<Error>(), Some(Error::InsufficientFunds { .. })));
+
+        ledger.escrow("game", &[max_loss(-40)?])?;
+        assert_eq!(ledger.get_balances(&player("player1")), balances(110));
+        assert_eq!(ledger.get_balances(&Account::Escrow("game".to_string())), balances(40));
+
+        ledger.release("game")?;
+        assert_eq!(ledger.get_balances(&player("player1")), balances(150));
+        Ok(())
+    }
+
+    #[test]
+    fn withdrawals_need_funds() -> Result<()> {
+        let ledger = Ledger::new();
+        ledger.deposit(player("player1"), eur(), Money::from_minor(50))?;
+
+        let error = ledger.withdraw(player("player1"), eur(), Money::from_minor(80)).err().unwrap();
+        assert!(matches!(error.downcast_ref::<Error>(), Some(Error::InsufficientFunds { .. })));
+
+        ledger.withdraw(player("player1"), eur(), Money::from_minor(30))?;
+
+        let balances: Vec<i64> = ledger.get_statement(&player("player1")).iter().map(|line| line.balance.get_minor()).collect();
+        assert_eq!(balances, vec![50, 20]);
+        assert_eq!(ledger.get_balances(&Account::External), BTreeMap::from([(eur(), Money::from_minor(-20))]));
+        Ok(())
+    }
+
+    #[test]
+    fn unbalanced_entries_are_refused() {
+        let mut books = Ledger::new().books.into_inner().unwrap();
+        let postings = vec![Posting { account: player("player1"), currency: eur(), amount: Money::from_minor(10) }];
+
+        assert!(books.record(EntryKind::Deposit, postings).is_err());
+        assert!(books.entries.is_empty());
+        assert!(books.balances.is_empty());
+    }
+
+    #[test]
+    fn reopen() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("zsonkorp-ledger-{}.jsonl", Uuid::new_v4()));
+
+        {
+            let ledger = Ledger::open(&path)?;
+            ledger.deposit(player("player1"), eur(), Money::from_minor(50))?;
+            ledger.settle("game", &[])?;
+        }
+
+        // a torn last entry is dropped and the next one is written in its place
+        let complete = std::fs::read_to_string(&path)?;
+        std::fs::write(&path, format!("{}{{\"kind\":", complete))?;
+
+        let ledger = Ledger::open(&path)?;
+        assert!(ledger.is_settled("game"));
+        ledger.withdraw(player("player1"), eur(), Money::from_minor(20))?;
+
+        let ledger = Ledger::open(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(ledger.get_balances(&player("player1")), BTreeMap::from([(eur(), Money::from_minor(30))]));
+        assert!(ledger.is_settled("game"));
+        assert_eq!(ledger.deposit(player("player1"), eur(), Money::from_minor(5))?, 3);
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+mod card;
+mod deck;
+mod game;
+mod config;
+mod state;
+mod player;
+mod game_storage;
+mod wager;
+mod payout;
+mod dto;
+mod transition;
+mod shuffler;
+mod fairness;
+mod validation;
+mod money;
+mod journal;
+pub mod handlers;
+pub mod app_state;
+pub mod sim;
+pub mod expiry;
+pub mod ledger;
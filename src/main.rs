@@ -1,38 +1,96 @@
-use axum::{routing::get, routing::post, Router};
-use crate::app_state::AppState;
-
-mod card;
-mod deck;
-mod game;
-mod config;
-mod state;
-mod player;
-mod game_storage;
-mod handlers;
-mod app_state;
-mod wager;
-mod payout;
-mod dto;
-mod transition;
+use anyhow::{anyhow, Context, Result};
+use axum::{routing::get, routing::patch, routing::post, Router};
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+use zsonkorp::app_state::AppState;
+use zsonkorp::expiry::{Evictor, ExpiryPolicy};
+use zsonkorp::handlers;
+use zsonkorp::ledger::Ledger;
 
 #[tokio::main]
-async fn main() {
-    let app_state = AppState::new();
+async fn main() -> Result<()> {
+    // games survive restarts when a journal file is configured
+    let app_state = match env::var("ZSONKORP_JOURNAL") {
+        Ok(path) => AppState::with_journal(&path).with_context(|| format!("Could not open the game journal {}", path))?,
+        Err(_) => AppState::new()
+    };
+
+    let defaults = ExpiryPolicy::default();
+    let policy = ExpiryPolicy {
+        setup_ttl: env_secs("ZSONKORP_SETUP_TTL_SECS")?.unwrap_or(defaults.setup_ttl),
+        ended_ttl: env_secs("ZSONKORP_ENDED_TTL_SECS")?.unwrap_or(defaults.ended_ttl),
+        max_games: env_parse("ZSONKORP_MAX_GAMES")?.unwrap_or(defaults.max_games),
+        interval: env_secs("ZSONKORP_EVICTION_INTERVAL_SECS")?.unwrap_or(defaults.interval)
+    };
+
+    // finished games are archived on eviction when an archive file is configured
+    let app_state = app_state.with_evictor(match env::var("ZSONKORP_ARCHIVE") {
+        Ok(path) => Evictor::with_archive(policy, &path).with_context(|| format!("Could not open the archive {}", path))?,
+        Err(_) => Evictor::new(policy)
+    });
+
+    // payouts of ended games settle into wallets when a ledger file is configured
+    let app_state = match env::var("ZSONKORP_LEDGER") {
+        Ok(path) => {
+            // houses may not have more than this in escrow when a limit is configured for the currency
+            let exposure_limits = env_parse("ZSONKORP_MAX_EXPOSURE")?.unwrap_or_default();
+            let ledger = Ledger::open(&path).with_context(|| format!("Could not open the ledger {}", path))?;
+            app_state.with_ledger(ledger.with_exposure_limits(exposure_limits))?
+        },
+        Err(_) => app_state
+    };
+
+    // games can be voided by operators when an operator key is configured
+    let app_state = match env::var("ZSONKORP_OPERATOR_KEY") {
+        Ok(operator_key) => app_state.with_operator_key(operator_key),
+        Err(_) => app_state
+    };
+
+    tokio::spawn(app_state.clone().evict_expired_games());
 
     // build our application with a single route
     let app = Router::new()
         .route("/", get(app_ascii_art))
         .route("/game", post(handlers::create_game))
+        .route("/game/:id", get(handlers::get_game))
         .route("/game/:id/payout", get(handlers::get_payout))
+        .route("/game/:id/state", get(handlers::get_state))
+        .route("/game/:id/odds", get(handlers::get_wager_odds))
+        .route("/game/:id/fairness", get(handlers::get_fairness))
+        .route("/game/:id/client_seeds", post(handlers::add_client_seeds))
+        .route("/odds/fts", post(handlers::price_fts_odds))
+        .route("/fairness/verify", post(handlers::verify_fairness))
+        .route("/game/:id/wagers", post(handlers::place_wager))
+        .route("/game/:id/wagers/:wager_id", patch(handlers::amend_wager).delete(handlers::cancel_wager))
         .route(
             "/game/:id/transitions",
             get(handlers::get_transitions).post(handlers::transition_game)
         )
+        .route("/ledger/:kind/:id/balance", get(handlers::get_balance))
+        .route("/ledger/:kind/:id/statement", get(handlers::get_statement))
+        .route("/ledger/:kind/:id/deposits", post(handlers::deposit))
+        .route("/ledger/:kind/:id/withdrawals", post(handlers::withdraw))
         .with_state(app_state);
 
     // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+// Unset variables give None, a value that does not parse is reported with the variable it came from
+fn env_parse<T: FromStr>(name: &str) -> Result<Option<T>>
+    where T::Err: Display
+{
+    env::var(name).ok()
+        .map(|value| value.parse().map_err(|e| anyhow!("Invalid {} value {:?}: {}", name, value, e)))
+        .transpose()
+}
+
+fn env_secs(name: &str) -> Result<Option<Duration>> {
+    Ok(env_parse(name)?.map(Duration::from_secs))
 }
 
 async fn app_ascii_art() -> &'static str {
@@ -0,0 +1,195 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+// ISO 4217 code of amounts that are not in any currency, used by wagers that do not name one
+pub const NO_CURRENCY: &str = "XXX";
+
+// ISO 4217 minor unit exponents differing from the usual 2 (cents), a major unit holds 10^exponent minor units
+const MINOR_UNIT_EXPONENTS: &[(&str, u32)] = &[
+    ("BIF", 0), ("CLP", 0), ("DJF", 0), ("GNF", 0), ("ISK", 0), ("JPY", 0), ("KMF", 0), ("KRW", 0), ("PYG", 0),
+    ("RWF", 0), ("UGX", 0), ("UYI", 0), ("VND", 0), ("VUV", 0), ("XAF", 0), ("XOF", 0), ("XPF", 0), (NO_CURRENCY, 0),
+    ("BHD", 3), ("IQD", 3), ("JOD", 3), ("KWD", 3), ("LYD", 3), ("OMR", 3), ("TND", 3),
+    ("CLF", 4), ("UYW", 4)
+];
+const DEFAULT_MINOR_UNIT_EXPONENT: u32 = 2;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("{0} {1} {2} overflows the largest supported amount")]
+    Overflow(Money, &'static str, String),
+    #[error("Invalid currency code: {0}, expected 3 uppercase letters")]
+    InvalidCurrency(String)
+}
+
+// A signed amount of minor units, every arithmetic operation is checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub fn from_minor(minor: i64) -> Self {
+        Money(minor)
+    }
+
+    pub fn get_minor(&self) -> i64 {
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.0 > 0
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money> {
+        self.0.checked_add(other.0)
+            .map(Money)
+            .ok_or_else(|| anyhow!(Error::Overflow(self, "+", other.to_string())))
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money> {
+        self.0.checked_sub(other.0)
+            .map(Money)
+            .ok_or_else(|| anyhow!(Error::Overflow(self, "-", other.to_string())))
+    }
+
+    pub fn checked_mul(self, factor: i64) -> Result<Money> {
+        self.0.checked_mul(factor)
+            .map(Money)
+            .ok_or_else(|| anyhow!(Error::Overflow(self, "*", factor.to_string())))
+    }
+
+    // Minor units as a float, for odds and statistics only
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64
+    }
+
+    // Displayed in major units of the currency, 1005 is 10.05 EUR but 1005 JPY
+    pub fn in_currency(self, currency: &Currency) -> InCurrency<'_> {
+        InCurrency { money: self, currency }
+    }
+}
+
+pub struct InCurrency<'a> {
+    money: Money,
+    currency: &'a Currency
+}
+
+// ISO 4217 alphabetic currency code, amounts in it are counted in its minor units
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct Currency(String);
+
+impl Default for Currency {
+    fn default() -> Self {
+        Currency(NO_CURRENCY.to_string())
+    }
+}
+
+impl Currency {
+    pub fn get_minor_unit_exponent(&self) -> u32 {
+        MINOR_UNIT_EXPONENTS.iter()
+            .find(|(code, _)| *code == self.0)
+            .map_or(DEFAULT_MINOR_UNIT_EXPONENT, |(_, exponent)| *exponent)
+    }
+}
+
+impl FromStr for Currency {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 3 || !s.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(Error::InvalidCurrency(s.to_string()));
+        }
+
+        Ok(Currency(s.to_string()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Minor units, as amounts are given in the API
+impl Display for Money {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Display for InCurrency<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let exponent = self.currency.get_minor_unit_exponent();
+        let sign = if self.money.0 < 0 { "-" } else { "" };
+        let minor = self.money.0.unsigned_abs();
+
+        if exponent == 0 {
+            return write!(f, "{}{}", sign, minor);
+        }
+
+        let unit = 10u64.pow(exponent);
+        write!(f, "{}{}.{:0width$}", sign, minor / unit, minor % unit, width = exponent as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_uses_the_currency_exponent() {
+        let currency = |code: &str| code.parse::<Currency>().unwrap();
+
+        assert_eq!(Money::from_minor(1005).in_currency(&currency("EUR")).to_string(), "10.05");
+        assert_eq!(Money::from_minor(-7).in_currency(&currency("USD")).to_string(), "-0.07");
+        assert_eq!(Money::from_minor(i64::MIN).in_currency(&currency("EUR")).to_string(), "-92233720368547758.08");
+        assert_eq!(Money::from_minor(1005).in_currency(&currency("JPY")).to_string(), "1005");
+        assert_eq!(Money::from_minor(1005).in_currency(&currency("KWD")).to_string(), "1.005");
+        assert_eq!(Money::from_minor(1005).in_currency(&Currency::default()).to_string(), "1005");
+        assert_eq!(Money::from_minor(-1005).to_string(), "-1005");
+    }
+
+    #[test]
+    fn checked_arithmetic() -> Result<()> {
+        assert_eq!(Money::from_minor(10).checked_mul(-17)?, Money::from_minor(-170));
+        assert_eq!(Money::from_minor(10).checked_sub(Money::from_minor(25))?, Money::from_minor(-15));
+
+        assert!(Money::from_minor(i64::MAX).checked_add(Money::from_minor(1)).is_err());
+        assert!(Money::from_minor(i64::MIN).checked_sub(Money::from_minor(1)).is_err());
+        assert!(Money::from_minor(i64::MAX / 2).checked_mul(3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn currency_codes() {
+        assert_eq!("EUR".parse::<Currency>().unwrap().to_string(), "EUR");
+        assert_eq!(Currency::default().to_string(), NO_CURRENCY);
+
+        for code in ["eur", "EURO", "E1R", ""] {
+            assert_eq!(code.parse::<Currency>(), Err(Error::InvalidCurrency(code.to_string())));
+        }
+    }
+
+    #[test]
+    fn serialized_as_minor_units() -> Result<()> {
+        assert_eq!(serde_json::to_string(&Money::from_minor(250))?, "250");
+        assert_eq!(serde_json::from_str::<Money>("-3")?, Money::from_minor(-3));
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -2,35 +2,60 @@ use serde::Serialize;
 use crate::wager::Wager;
 use anyhow::{anyhow, Result};
 use thiserror::Error;
+use crate::money::{Currency, Money};
 
 #[derive(Error, Debug)]
 enum Error {
     #[error("Amount must not be zero")]
     ZeroAmount,
     #[error("Player({0})'s loss: {1} must not exceeds wager amount: {2}")]
-    LossExceedsWagerAmount(String, i32, i32),
+    LossExceedsWagerAmount(String, Money, Money),
 }
 
 #[derive(Debug, Serialize)]
 pub struct Payout<'a>{
     player_id: &'a str,
     wager_id: Option<u32>,
-    amount: i32     //positive -> player wins, negative -> player loses
+    currency: &'a Currency,
+    amount: Money     //positive -> player wins, negative -> player loses
 }
 
 impl<'a> Payout<'a> {
-    pub fn new<T>(player_id: &'a str, opt_wager: Option<&Wager<T>>, amount: i32) -> Result<Self> {
+    // Amounts are only ever added up within the same currency, the house gets a payout per currency
+    pub fn new<T>(player_id: &'a str, opt_wager: Option<&Wager<T>>, currency: &'a Currency, amount: Money) -> Result<Self> {
 
-        if amount == 0 {
+        if amount.is_zero() {
             return Err(anyhow!(Error::ZeroAmount));
         }
 
         if let Some(wager) = opt_wager {
-            return Ok( Payout{ player_id, wager_id: Some(*wager.get_id()), amount } );
+            return Ok( Payout{ player_id, wager_id: Some(*wager.get_id()), currency, amount } );
         }
 
         // No wager? This happens when we are creating payout for the house
         // Maybe separate into another function to skip Option<wager> check
-        Ok(Payout { player_id, wager_id: None, amount })
+        Ok(Payout { player_id, wager_id: None, currency, amount })
+    }
+
+    // A wager of a voided game, the stake goes back to the player and nothing else moves. Unlike `new` the amount
+    // is zero on purpose, the record is kept so that every wager of the game shows up in its payouts.
+    pub fn refund<T>(player_id: &'a str, wager: &'a Wager<T>) -> Self {
+        Payout { player_id, wager_id: Some(*wager.get_id()), currency: wager.get_currency(), amount: Money::ZERO }
+    }
+
+    pub fn get_player_id(&self) -> &'a str {
+        self.player_id
+    }
+
+    pub fn get_wager_id(&self) -> Option<u32> {
+        self.wager_id
+    }
+
+    pub fn get_currency(&self) -> &'a Currency {
+        self.currency
+    }
+
+    pub fn get_amount(&self) -> Money {
+        self.amount
     }
 }
\ No newline at end of file
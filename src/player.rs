@@ -1,9 +1,9 @@
 use std::fmt::Formatter;
 use std::hash::{Hash, Hasher};
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde::de::{Error, Visitor};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Player {
     id: String
 }
@@ -34,6 +34,14 @@ impl Eq for Player {
 
 }
 
+impl Serialize for Player {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_str(&self.id)
+    }
+}
+
 struct PlayerVisitor {}
 impl PlayerVisitor {
     pub fn new() -> Self {
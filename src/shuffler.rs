@@ -0,0 +1,65 @@
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+pub type Seed = [u8; 32];
+
+// Shuffles with a ChaCha20 stream keyed by a recorded seed, so any shuffle can be replayed from its seed
+#[derive(Serialize, Deserialize)]
+pub struct Shuffler {
+    seed: Seed,
+    rng: ChaCha20Rng
+}
+
+impl Shuffler {
+    // Seeded by the OS, this is what live games use
+    pub fn from_entropy() -> Self {
+        let mut seed = Seed::default();
+        OsRng.fill_bytes(&mut seed);
+
+        Shuffler::from_seed(seed)
+    }
+
+    pub fn from_seed(seed: Seed) -> Self {
+        Shuffler { seed, rng: ChaCha20Rng::from_seed(seed) }
+    }
+
+    pub fn get_seed(&self) -> &Seed {
+        &self.seed
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        items.shuffle(&mut self.rng);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_order() {
+        let mut first: Vec<u8> = (0..52).collect();
+        let mut second = first.clone();
+
+        Shuffler::from_seed([7; 32]).shuffle(&mut first);
+        Shuffler::from_seed([7; 32]).shuffle(&mut second);
+
+        assert_eq!(first, second);
+        assert_ne!(first, (0..52).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn seed_is_recorded() {
+        let mut shuffler = Shuffler::from_entropy();
+        let mut items: Vec<u8> = (0..52).collect();
+        let mut replayed = items.clone();
+
+        Shuffler::from_seed(*shuffler.get_seed()).shuffle(&mut replayed);
+        shuffler.shuffle(&mut items);
+
+        assert_eq!(items, replayed);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use crate::game;
+use crate::game::{Game, GameType};
+use crate::money::Money;
+use crate::shuffler::Shuffler;
+use crate::transition::GameTransition::End;
+use crate::transition::Transition;
+
+// Results of a single player over every round, rounds without a payout count as 0
+#[derive(Debug, Serialize)]
+pub struct PlayerReport {
+    pub mean: f64,
+    pub variance: f64,
+    pub hit_frequency: f64
+}
+
+// House edges are the house result over the amount the wagers put at risk every round
+#[derive(Debug, Serialize)]
+pub struct SimulationReport {
+    pub rounds: u64,
+    pub players: BTreeMap<String, PlayerReport>,
+    pub house: PlayerReport,
+    pub amount_at_risk: f64,
+    pub expected_house_edge: f64,
+    pub observed_house_edge: f64
+}
+
+#[derive(Default)]
+struct Accumulator {
+    sum: f64,
+    sum_squares: f64,
+    hits: u64
+}
+
+impl Accumulator {
+    fn add(&mut self, amount: Money) {
+        self.sum += amount.to_f64();
+        self.sum_squares += amount.to_f64() * amount.to_f64();
+
+        if amount.is_positive() {
+            self.hits += 1;
+        }
+    }
+
+    fn report(&self, rounds: u64) -> PlayerReport {
+        let mean = self.sum / rounds as f64;
+
+        PlayerReport {
+            mean,
+            variance: self.sum_squares / rounds as f64 - mean * mean,
+            hit_frequency: self.hits as f64 / rounds as f64
+        }
+    }
+}
+
+// Plays `rounds` games created from `config`, always taking the first valid transition the game accepts until it is over
+pub fn run(kind: &str, config: &str, rounds: u64) -> Result<SimulationReport> {
+    if rounds == 0 {
+        return Err(anyhow!("At least one round must be simulated"));
+    }
+
+    let game_type: GameType = serde_json::from_value(serde_json::Value::String(kind.to_string()))
+        .map_err(|_| anyhow!("Unknown game type: {}", kind))?;
+
+    let house_id = serde_json::from_str::<serde_json::Value>(config)?
+        .get("house_id")
+        .and_then(|house_id| house_id.as_str())
+        .ok_or(anyhow!("The config has no house id"))?
+        .to_string();
+
+    let (amount_at_risk, expected_house_win) = game::create_game(&game_type, config, Shuffler::from_entropy())?
+        .get_wager_odds()
+        .iter()
+        .fold((0.0, 0.0), |(at_risk, house_win), odds| (at_risk + odds.max_loss, house_win - odds.expected_payout));
+
+    let mut accumulators: BTreeMap<String, Accumulator> = BTreeMap::new();
+
+    for _ in 0..rounds {
+        let mut game = game::create_game(&game_type, config, Shuffler::from_entropy())?;
+        play(game.as_mut())?;
+
+        let mut round: BTreeMap<&str, Money> = BTreeMap::new();
+        let payouts = game.get_payout()?;
+
+        // results are added up per player, which only makes sense in a single currency
+        if payouts.iter().any(|payout| payout.get_currency() != payouts[0].get_currency()) {
+            return Err(anyhow!("Games mixing currencies cannot be simulated"));
+        }
+
+        for payout in payouts {
+            let total = round.entry(payout.get_player_id()).or_insert(Money::ZERO);
+            *total = total.checked_add(payout.get_amount())?;
+        }
+
+        round.entry(&house_id).or_insert(Money::ZERO);
+
+        for (player_id, amount) in round {
+            accumulators.entry(player_id.to_string()).or_default().add(amount);
+        }
+    }
+
+    // players missing from a round had a 0 result in it
+    let house = accumulators.remove(&house_id).unwrap_or_default().report(rounds);
+    let players: BTreeMap<String, PlayerReport> = accumulators.into_iter()
+        .map(|(player_id, accumulator)| (player_id, accumulator.report(rounds)))
+        .collect();
+
+    Ok(SimulationReport {
+        rounds,
+        players,
+        observed_house_edge: house.mean / amount_at_risk,
+        house,
+        amount_at_risk,
+        expected_house_edge: expected_house_win / amount_at_risk
+    })
+}
+
+fn play(game: &mut dyn Game) -> Result<()> {
+    while !game.get_valid_transitions().is_empty() {
+        let accepted = game.get_valid_transitions().into_iter().any(|transition| game.transition(transition).is_ok());
+
+        // a Cta game whose listed cuts all tie cannot end, it is voided like an operator would
+        if !accepted {
+            game.transition(Transition::Game(End { reason: "no valid transition was accepted".to_string() }))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FTS_CONFIG: &str = r#"{
+        "wagers": {
+            "player1": [{ "id": 0, "wager_type": "FullDeck", "amount": 10 }],
+            "player2": [{ "id": 1, "wager_type": { "FlopRange": [0, 3] }, "amount": 20 }]
+        },
+        "house_id": "house"
+    }"#;
+
+    #[test]
+    fn simulate_fts() -> Result<()> {
+        let report = run("Fts", FTS_CONFIG, 500)?;
+
+        assert_eq!(report.rounds, 500);
+        assert_eq!(report.players.len(), 2);
+        assert_eq!(report.amount_at_risk, 10.0 * 17.0 + 20.0 * 4.0);
+
+        // the house pays exactly what the players win
+        let player_mean: f64 = report.players.values().map(|player| player.mean).sum();
+        assert!((report.house.mean + player_mean).abs() < 1e-9);
+        assert!(report.expected_house_edge > 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn simulate_cta() -> Result<()> {
+        let config = r#"{
+            "wagers": { "player1": [{ "id": 0, "wager_type": "Reverse", "amount": 10 }] },
+            "house_id": "house"
+        }"#;
+        let report = run("Cta", config, 200)?;
+
+        // even money wagers only ever win or lose their amount
+        let player = &report.players["player1"];
+        assert!(player.mean.abs() <= 10.0);
+        assert!(player.variance <= 100.0 + 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_simulation() {
+        assert!(run("Poker", FTS_CONFIG, 10).is_err());
+        assert!(run("Fts", FTS_CONFIG, 0).is_err());
+        assert!(run("Fts", "{}", 10).is_err());
+    }
+}
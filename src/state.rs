@@ -1,17 +1,29 @@
 mod cta;
 
+use serde::{Deserialize, Serialize};
 pub use cta::CtaState;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum GameState {
     Setup,
     Started,
-    Ended
+    Ended,
+    // Aborted by an operator, every wager is refunded
+    Voided {
+        reason: String
+    }
 }
 
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum State {
     Game(GameState),
     Cta(CtaState)
+}
+
+impl State {
+    // Ended and voided games take no more transitions and can be settled
+    pub fn is_over(&self) -> bool {
+        matches!(self, State::Game(GameState::Ended | GameState::Voided { .. }))
+    }
 }
\ No newline at end of file
@@ -1,4 +1,6 @@
-#[derive(Debug, Eq, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum CtaState {
     AwaitCut
 }
\ No newline at end of file
@@ -3,13 +3,16 @@ mod cta;
 use serde::{Deserialize, Serialize};
 pub use cta::CtaTransition;
 
-#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub enum GameTransition {
     Start,
-    End
+    // Operator only, voids a game that is not over yet
+    End {
+        reason: String
+    }
 }
 
-#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Transition {
     Game(GameTransition),
     Cta(CtaTransition)
@@ -1,7 +1,6 @@
 use serde::{Deserialize, Serialize};
-use crate::transition::{GameTransition, Transition};
 
-#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub enum CtaTransition {
     Cut {
         deck_index: u8,
@@ -0,0 +1,94 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_path_to_error::Segment;
+use thiserror::Error;
+
+// A single problem of a request body, `path` is a JSON pointer (RFC 6901) to the offending value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    pub path: String,
+    pub message: String
+}
+
+// Every problem found while validating a request, sorted by path
+#[derive(Error, Debug)]
+#[error("Invalid configuration: {}", .0.iter().map(|v| format!("{} {}", v.path, v.message)).collect::<Vec<String>>().join(", "))]
+pub struct ValidationErrors(pub Vec<Violation>);
+
+#[derive(Default)]
+pub struct Violations(Vec<Violation>);
+
+impl Violations {
+    pub fn new() -> Self {
+        Violations(Vec::new())
+    }
+
+    pub fn add(&mut self, path: String, message: impl ToString) {
+        self.0.push(Violation { path, message: message.to_string() });
+    }
+
+    pub fn append(&mut self, other: Violations) {
+        self.0.extend(other.0);
+    }
+
+    pub fn into_result(mut self) -> Result<(), ValidationErrors> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        self.0.sort_by(|a, b| a.path.cmp(&b.path));
+        Err(ValidationErrors(self.0))
+    }
+
+    // Deserializes `value`, recording the error at `path` followed by the position serde failed at
+    pub fn deserialize<T: DeserializeOwned>(&mut self, path: &str, value: &serde_json::Value) -> Option<T> {
+        match serde_path_to_error::deserialize(value) {
+            Ok(deserialized) => Some(deserialized),
+            Err(e) => {
+                let suffix: String = e.path().iter()
+                    .filter_map(|segment| match segment {
+                        Segment::Seq { index } => Some(index.to_string()),
+                        Segment::Map { key } => Some(key.clone()),
+                        Segment::Enum { variant } => Some(variant.clone()),
+                        Segment::Unknown => None
+                    })
+                    .map(|segment| pointer(&[&segment]))
+                    .collect();
+
+                self.add(format!("{}{}", path, suffix), e.inner());
+                None
+            }
+        }
+    }
+}
+
+// Builds a JSON pointer out of unescaped segments
+pub fn pointer(segments: &[&str]) -> String {
+    segments.iter().map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1"))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pointer_escaping() {
+        assert_eq!(pointer(&["wagers", "a/b~c", "0"]), "/wagers/a~1b~0c/0");
+        assert_eq!(pointer(&[]), "");
+    }
+
+    #[test]
+    fn nested_serde_errors() {
+        let mut violations = Violations::new();
+        let value = serde_json::json!({ "odds": { "full_deck": "many" } });
+
+        #[derive(serde::Deserialize)]
+        struct Odds { #[allow(dead_code)] full_deck: i32 }
+        #[derive(serde::Deserialize)]
+        struct Settings { #[allow(dead_code)] odds: Odds }
+
+        assert!(violations.deserialize::<Settings>("/root", &value).is_none());
+        let ValidationErrors(violations) = violations.into_result().unwrap_err();
+        assert_eq!(violations[0].path, "/root/odds/full_deck");
+    }
+}
@@ -1,24 +1,27 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use anyhow::{anyhow, Result};
+use crate::money::{Currency, Money};
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Wager<T> {
     id: u32,
     wager_type: T,
-    pub amount: i32
+    pub amount: Money,
+    #[serde(default)]
+    currency: Currency
 }
 
 impl<T> Wager<T> {
-    pub fn new(id: u32, wager_type: T, amount: i32) -> Result<Self> {
-        if amount == 0 {
+    pub fn new(id: u32, wager_type: T, amount: Money) -> Result<Self> {
+        if amount.is_zero() {
             return Err(anyhow!("0 wager amount is not allowed"));
         }
 
-        if amount < 0 {
+        if !amount.is_positive() {
             return Err(anyhow!("negative wager amount is not allowed"));
         }
 
-        Ok(Wager { id, wager_type, amount })
+        Ok(Wager { id, wager_type, amount, currency: Currency::default() })
     }
 
     pub fn get_wager_type(&self) -> &T {
@@ -29,7 +32,11 @@ impl<T> Wager<T> {
         &self.id
     }
 
-    pub fn get_amount(&self) -> &i32 {
+    pub fn get_currency(&self) -> &Currency {
+        &self.currency
+    }
+
+    pub fn get_amount(&self) -> &Money {
         &self.amount
     }
 }
\ No newline at end of file